@@ -0,0 +1,21 @@
+#! /bin/sh
+
+# Replaces the embedded ISBN range table by the latest RangeMessage.xml
+# published by the International ISBN Agency (kept unchanged).
+
+set -e
+
+URL="https://www.isbn-international.org/export_rangemessage.xml"
+TARGET="$(dirname "$0")/../resources/RangeMessage.xml"
+
+curl -sSfL -o "$TARGET.tmp" "$URL"
+
+if ! grep -q "<ISBNRangeMessage>" "$TARGET.tmp"; then
+  echo "Unexpected content from $URL" >&2
+  rm -f "$TARGET.tmp"
+  exit 1
+fi
+
+mv "$TARGET.tmp" "$TARGET"
+
+echo "Updated $TARGET"
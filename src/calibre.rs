@@ -0,0 +1,355 @@
+//! Calibre library, either from its `metadata.db` catalog (SQLite)
+//! or from the `metadata.opf` file of each book directory.
+//!
+//! The covers are referenced as the local `cover.jpg` files,
+//! or else as Google Books URLs if the book has a Google identifier.
+
+use std::fs::{self, File};
+use std::io::{BufReader, Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use rusqlite::{Connection, OpenFlags, params};
+
+use crate::codex::{Author, Book, author_named, author_sorted, empty_book};
+use crate::model::Isbn;
+use crate::opf;
+
+pub const METADATA_DB: &str = "metadata.db";
+pub const METADATA_OPF: &str = "metadata.opf";
+
+/// Name of the cover image in a book directory
+const COVER_FILE: &str = "cover.jpg";
+
+const GOOGLE_COVER_URL: &str = "https://books.google.com/books/content?printsec=frontcover&img=1&zoom=1&id=";
+
+/// Reads the books of a Calibre library, given either its directory
+/// (using the `metadata.db` catalog if any, or else the `metadata.opf` files),
+/// or a catalog or OPF file.
+pub fn read<P: AsRef<Path>>(
+    path: P,
+) -> Result<Box<dyn Iterator<Item = Result<Book>>>> {
+    let p = path.as_ref();
+    let db = p.join(METADATA_DB);
+
+    if p.is_dir() && !db.is_file() {
+        opf_files(p).map(|files| Box::new(files.into_iter().map(read_opf)) as
+                         Box<dyn Iterator<Item = Result<Book>>>)
+    } else if p.extension().is_some_and(|e| e == "opf") {
+        read_opf(p).map(|b| Box::new(std::iter::once(Ok(b))) as
+                        Box<dyn Iterator<Item = Result<Book>>>)
+    } else {
+        read_db(if p.is_dir() { &db } else { p }).
+            map(|books| Box::new(books.into_iter().map(Ok)) as
+                Box<dyn Iterator<Item = Result<Book>>>)
+    }
+}
+
+// ---
+
+/// Returns the `metadata.opf` files in the library directory
+/// (e.g. `Author/Title (id)/metadata.opf`), in path order.
+fn opf_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?.
+        map(|e| e.map(|e| e.path())).collect::<Result<Vec<PathBuf>>>()?;
+
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            files.extend(opf_files(&path)?);
+        } else if path.file_name().is_some_and(|n| n == METADATA_OPF) {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
+/// Reads the book from its `metadata.opf`,
+/// with the cover from the book directory.
+pub fn read_opf<P: AsRef<Path>>(path: P) -> Result<Book> {
+    let p = path.as_ref();
+
+    let package = File::open(p).and_then(|f| opf::parse(BufReader::new(f))).
+        map_err(|cause| Error::new(cause.kind(), format!(
+            "Fails to read '{}': {}", p.display(), cause)))?;
+
+    let dir = p.parent().unwrap_or_else(|| Path::new("."));
+    let mut book = package.book;
+
+    let cover = package.cover.map(|c| dir.join(c.href)).
+        filter(|c| c.is_file()).
+        or_else(|| Some(dir.join(COVER_FILE)).filter(|c| c.is_file()));
+
+    book.cover = cover_reference(cover, package.google_id);
+
+    Ok(book)
+}
+
+fn cover_reference(file: Option<PathBuf>, google_id: Option<String>) -> String {
+    match (file, google_id) {
+        (Some(f), _) => f.display().to_string(),
+        (None, Some(id)) => format!("{}{}", GOOGLE_COVER_URL, id),
+        (None, None) => "".to_string(),
+    }
+}
+
+// ---
+
+fn db_error(cause: rusqlite::Error) -> Error {
+    Error::new(ErrorKind::InvalidData,
+               format!("Invalid Calibre database: {}", cause))
+}
+
+/// Book row of the catalog
+struct Row {
+    id: i64,
+    title: String,
+    pubdate: Option<String>,
+    series_index: Option<f64>,
+    /// Directory of the book, relative to the library
+    path: String,
+    has_cover: bool,
+    series: Option<String>,
+    publisher: Option<String>,
+    /// From 0 to 10
+    rating: Option<i64>,
+    comments: Option<String>,
+    language: Option<String>,
+}
+
+const BOOKS_QUERY: &str = "SELECT b.id, b.title, b.pubdate, b.series_index, b.path, b.has_cover,
+  (SELECT s.name FROM books_series_link l JOIN series s ON s.id = l.series WHERE l.book = b.id),
+  (SELECT p.name FROM books_publishers_link l JOIN publishers p ON p.id = l.publisher WHERE l.book = b.id),
+  (SELECT r.rating FROM books_ratings_link l JOIN ratings r ON r.id = l.rating WHERE l.book = b.id),
+  (SELECT c.text FROM comments c WHERE c.book = b.id),
+  (SELECT g.lang_code FROM books_languages_link l JOIN languages g ON g.id = l.lang_code WHERE l.book = b.id ORDER BY l.item_order)
+FROM books b ORDER BY b.id";
+
+/// Reads the books from the `metadata.db` catalog,
+/// the covers being looked up in the library directory (the catalog one).
+pub fn read_db<P: AsRef<Path>>(path: P) -> Result<Vec<Book>> {
+    let p = path.as_ref();
+    let library = p.parent().unwrap_or_else(|| Path::new("."));
+
+    let db = Connection::open_with_flags(p, OpenFlags::SQLITE_OPEN_READ_ONLY).
+        map_err(|cause| Error::new(ErrorKind::NotFound, format!(
+            "Fails to open '{}': {}", p.display(), cause)))?;
+
+    let rows = db.prepare(BOOKS_QUERY).and_then(|mut stmt| stmt.query_map(
+        params![],
+        |r| Ok(Row {
+            id: r.get(0)?,
+            title: r.get::<_, Option<String>>(1)?.unwrap_or_default(),
+            pubdate: r.get(2)?,
+            series_index: r.get(3)?,
+            path: r.get::<_, Option<String>>(4)?.unwrap_or_default(),
+            has_cover: r.get::<_, Option<bool>>(5)?.unwrap_or(false),
+            series: r.get(6)?,
+            publisher: r.get(7)?,
+            rating: r.get(8)?,
+            comments: r.get(9)?,
+            language: r.get(10)?,
+        }))?.collect::<rusqlite::Result<Vec<Row>>>()).map_err(db_error)?;
+
+    rows.into_iter().map(|row| read_row(&db, library, row)).collect()
+}
+
+fn read_row(db: &Connection, library: &Path, row: Row) -> Result<Book> {
+    let mut book = empty_book();
+
+    book.title = row.title;
+    book.authors = authors(db, row.id).map_err(db_error)?;
+    book.pubdate = row.pubdate.as_deref().and_then(opf::parse_date);
+    book.publisher = row.publisher.unwrap_or_default();
+    book.summary = row.comments.unwrap_or_default();
+    book.language = row.language.unwrap_or_default();
+    book.rating = row.rating.filter(|r| *r > 0).map(|r| r as f32 / 2.0);
+
+    if let Some(series) = row.series {
+        book.series = series;
+        book.volume = row.series_index.
+            filter(|i| i.fract() == 0.0 && *i >= 0.0 && *i <= u16::MAX as f64).
+            map(|i| i as u16);
+    }
+
+    book.tags = tags(db, row.id).map_err(db_error)?;
+
+    let mut google_id = None;
+
+    for (tpe, value) in identifiers(db, row.id).map_err(db_error)? {
+        match tpe.as_str() {
+            "isbn" => match Isbn::parse(&value) {
+                Ok(i) if !book.isbn.contains(&i) => book.isbn.push(i),
+                Ok(_) => (),
+
+                Err(cause) => log::warn!(
+                    target: "calibre", "Invalid ISBN '{}' for '{}': {}",
+                    value, book.title, cause),
+            },
+
+            "google" => google_id = Some(value),
+            _ => (),
+        }
+    }
+
+    let cover = if row.has_cover {
+        Some(library.join(&row.path).join(COVER_FILE)).filter(|c| c.is_file())
+    } else {
+        None
+    };
+
+    book.cover = cover_reference(cover, google_id);
+
+    Ok(book)
+}
+
+fn authors(db: &Connection, book: i64) -> rusqlite::Result<Vec<Author>> {
+    let mut stmt = db.prepare("SELECT a.name, a.sort FROM books_authors_link l JOIN authors a ON a.id = l.author WHERE l.book = ? ORDER BY l.id")?;
+
+    let rows = stmt.query_map(params![book], |r| Ok((
+        r.get::<_, Option<String>>(0)?.unwrap_or_default(),
+        r.get::<_, Option<String>>(1)?.unwrap_or_default())))?;
+
+    // The names are stored with '|' instead of ','
+    rows.map(|r| r.map(|(name, sort)| if sort.is_empty() {
+        author_named(&name.replace('|', ","))
+    } else {
+        author_sorted(&sort, &name.replace('|', ","))
+    })).collect()
+}
+
+fn identifiers(db: &Connection, book: i64) -> rusqlite::Result<Vec<(String, String)>> {
+    let mut stmt = db.prepare(
+        "SELECT type, val FROM identifiers WHERE book = ? ORDER BY id")?;
+
+    let rows = stmt.query_map(params![book], |r| Ok((r.get(0)?, r.get(1)?)))?;
+
+    rows.collect()
+}
+
+fn tags(db: &Connection, book: i64) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = db.prepare("SELECT t.name FROM books_tags_link l JOIN tags t ON t.id = l.tag WHERE l.book = ? ORDER BY t.name")?;
+    let rows = stmt.query_map(params![book], |r| r.get(0))?;
+
+    rows.collect()
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = "
+CREATE TABLE books (id INTEGER PRIMARY KEY, title TEXT, sort TEXT, pubdate TIMESTAMP, series_index REAL NOT NULL DEFAULT 1.0, path TEXT NOT NULL DEFAULT '', has_cover BOOL DEFAULT 0);
+CREATE TABLE authors (id INTEGER PRIMARY KEY, name TEXT, sort TEXT);
+CREATE TABLE books_authors_link (id INTEGER PRIMARY KEY, book INTEGER, author INTEGER);
+CREATE TABLE series (id INTEGER PRIMARY KEY, name TEXT, sort TEXT);
+CREATE TABLE books_series_link (id INTEGER PRIMARY KEY, book INTEGER, series INTEGER);
+CREATE TABLE publishers (id INTEGER PRIMARY KEY, name TEXT, sort TEXT);
+CREATE TABLE books_publishers_link (id INTEGER PRIMARY KEY, book INTEGER, publisher INTEGER);
+CREATE TABLE ratings (id INTEGER PRIMARY KEY, rating INTEGER);
+CREATE TABLE books_ratings_link (id INTEGER PRIMARY KEY, book INTEGER, rating INTEGER);
+CREATE TABLE tags (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE books_tags_link (id INTEGER PRIMARY KEY, book INTEGER, tag INTEGER);
+CREATE TABLE comments (id INTEGER PRIMARY KEY, book INTEGER, text TEXT);
+CREATE TABLE identifiers (id INTEGER PRIMARY KEY, book INTEGER, type TEXT, val TEXT);
+CREATE TABLE languages (id INTEGER PRIMARY KEY, lang_code TEXT);
+CREATE TABLE books_languages_link (id INTEGER PRIMARY KEY, book INTEGER, lang_code INTEGER, item_order INTEGER);
+
+INSERT INTO books VALUES (1, 'Accros du roc', 'Accros du roc', '2004-01-07 23:00:00+00:00', 27.0, 'Terry Pratchett/Accros du roc (1)', 1);
+INSERT INTO books VALUES (2, 'De bons présages', 'De bons présages', '0101-01-01 00:00:00+00:00', 1.0, 'Terry Pratchett/De bons presages (2)', 0);
+INSERT INTO authors VALUES (1, 'Terry Pratchett', 'Pratchett, Terry'), (2, 'Neil Gaiman', 'Gaiman, Neil');
+INSERT INTO books_authors_link VALUES (1, 1, 1), (2, 2, 1), (3, 2, 2);
+INSERT INTO series VALUES (1, 'Les Annales du Disque-monde', 'Annales du Disque-monde, Les');
+INSERT INTO books_series_link VALUES (1, 1, 1);
+INSERT INTO publishers VALUES (1, 'L''Atalante', 'Atalante');
+INSERT INTO books_publishers_link VALUES (1, 1, 1);
+INSERT INTO ratings VALUES (1, 8);
+INSERT INTO books_ratings_link VALUES (1, 1, 1);
+INSERT INTO tags VALUES (1, 'Humour'), (2, 'Fantasy');
+INSERT INTO books_tags_link VALUES (1, 1, 1), (2, 1, 2), (3, 2, 2);
+INSERT INTO comments VALUES (1, 1, '<p>Suzanne Sto Hélit.</p>');
+INSERT INTO identifiers VALUES (1, 1, 'isbn', '9782841720484'), (2, 2, 'google', 'fwIHPwAACAAJ'), (3, 2, 'isbn', '2-290-01212');
+INSERT INTO languages VALUES (1, 'fra');
+INSERT INTO books_languages_link VALUES (1, 1, 1, 0);
+";
+
+    #[test]
+    fn test_read_db() {
+        let library = tempfile::tempdir().unwrap();
+        let book_dir = library.path().join("Terry Pratchett/Accros du roc (1)");
+
+        fs::create_dir_all(&book_dir).unwrap();
+        fs::write(book_dir.join(COVER_FILE), b"\xFF\xD8\xFF").unwrap();
+
+        Connection::open(library.path().join(METADATA_DB)).unwrap().
+            execute_batch(SCHEMA).unwrap();
+
+        let books: Vec<Book> = read(library.path()).unwrap().
+            collect::<Result<Vec<Book>>>().unwrap();
+
+        assert_eq!(books.len(), 2);
+
+        let b = &books[0];
+
+        assert_eq!(b.title, "Accros du roc");
+        assert_eq!(b.authors.len(), 1);
+        assert_eq!(b.authors[0].first_name, "Terry");
+        assert_eq!(b.authors[0].last_name, "Pratchett");
+        assert_eq!(b.pubdate, Some(time::date!(2004-01-07)));
+        assert_eq!(b.series, "Les Annales du Disque-monde");
+        assert_eq!(b.volume, Some(27));
+        assert_eq!(b.publisher, "L'Atalante");
+        assert_eq!(b.rating, Some(4.0));
+        assert_eq!(b.tags, vec![ "Fantasy", "Humour" ]);
+        assert_eq!(b.summary, "<p>Suzanne Sto Hélit.</p>");
+        assert_eq!(b.language, "fra");
+        assert_eq!(b.isbn.iter().map(Isbn::digits).collect::<Vec<String>>(),
+                   vec![ "9782841720484" ]);
+        assert_eq!(b.cover, book_dir.join(COVER_FILE).display().to_string());
+
+        let b = &books[1];
+
+        assert_eq!(b.authors.iter().map(|a| a.name.as_str()).
+                   collect::<Vec<&str>>(),
+                   vec![ "Terry Pratchett", "Neil Gaiman" ]);
+        assert_eq!(b.pubdate, None);
+        assert_eq!(b.series, "");
+        assert_eq!(b.volume, None);
+        assert_eq!(b.rating, None);
+        assert!(b.isbn.is_empty());
+        assert_eq!(b.cover, format!("{}fwIHPwAACAAJ", GOOGLE_COVER_URL));
+    }
+
+    #[test]
+    fn test_read_opf() {
+        let library = tempfile::tempdir().unwrap();
+        let book_dir = library.path().join("Terry Pratchett/Accros du roc (1)");
+
+        fs::create_dir_all(&book_dir).unwrap();
+        fs::create_dir_all(library.path().join("Terry Pratchett/Ally (2)")).unwrap();
+        fs::write(book_dir.join(COVER_FILE), b"\xFF\xD8\xFF").unwrap();
+
+        fs::write(book_dir.join(METADATA_OPF), r#"<?xml version='1.0' encoding='utf-8'?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+    <dc:title>Accros du roc</dc:title>
+    <dc:creator opf:file-as="Pratchett, Terry" opf:role="aut">Terry Pratchett</dc:creator>
+  </metadata>
+</package>"#).unwrap();
+
+        let books: Vec<Book> = read(library.path()).unwrap().
+            collect::<Result<Vec<Book>>>().unwrap();
+
+        assert_eq!(books.len(), 1);
+        assert_eq!(books[0].title, "Accros du roc");
+        assert_eq!(books[0].cover,
+                   book_dir.join(COVER_FILE).display().to_string());
+
+        assert_eq!(read(library.path().join("missing.db")).err().
+                   map(|e| e.kind()), Some(ErrorKind::NotFound));
+    }
+}
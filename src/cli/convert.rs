@@ -0,0 +1,343 @@
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use codex_mylib::{calibre, codex, epub, goodreads};
+use codex_mylib::mylib::{self, dialect::Dialect};
+use codex_mylib::pipeline::{BookSink, BookSource, Books, Event, Pipeline};
+
+use super::STDIO;
+use super::report::{Report, Status};
+
+const CODEX: &str = "codex";
+const MYLIB: &str = "mylib";
+const GOODREADS: &str = "goodreads";
+const CALIBRE: &str = "calibre";
+const EPUB: &str = "epub";
+
+/// Directions of the conversion, as `<source format>-<sink format>`
+const DIRECTIONS: [&str; 12] = [
+    "codex-mylib", "mylib-codex",
+    "goodreads-mylib", "mylib-goodreads",
+    "codex-goodreads", "goodreads-codex",
+    "calibre-mylib", "calibre-codex", "calibre-goodreads",
+    "epub-mylib", "epub-codex", "epub-goodreads",
+];
+
+/// Base name of the output files when reading the standard input
+const STDIN_BASENAME: &str = "books";
+
+pub fn command<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("convert").
+        about("Converts Codex XML to Mylib (or between Codex XML, Mylib and Goodreads CSV, or from a Calibre library or EPUB files)").
+        arg(super::input_arg("Path to Codex XML file (or Mylib or Goodreads CSV file, Calibre library, EPUB file or directory), - for the standard input")).
+        args(&super::codex_args()).
+        arg(Arg::with_name("OUTPUT_DIR").
+            short("o").
+            long("output").
+            help("Path to directory where to write the output files, named after the input file (default: current directory)").
+            takes_value(true).
+            required(false)).
+        arg(Arg::with_name("CSV_OUT").
+            long("csv-out").
+            help("Path to Mylib (or Goodreads) CSV file to be written, - for the standard output (default: <output>/<input name>-mylib.csv or -goodreads.csv)").
+            takes_value(true).
+            required(false)).
+        arg(Arg::with_name("IMAGES_OUT").
+            long("images-out").
+            help("Path to Mylib images file to be written, - for the standard output (default: <output>/<input name>-mylib-images.txt)").
+            takes_value(true).
+            required(false)).
+        arg(Arg::with_name("XML_OUT").
+            long("xml-out").
+            help("Path to Codex XML file to be written when converting to Codex, - for the standard output (default: <output>/<input name>-codex.xml)").
+            takes_value(true).
+            required(false)).
+        arg(Arg::with_name("DIRECTION").
+            short("d").
+            long("direction").
+            help("Direction of the conversion").
+            takes_value(true).
+            possible_values(&DIRECTIONS).
+            default_value(DIRECTIONS[0])).
+        arg(Arg::with_name("IMAGES_FILE").
+            long("images").
+            help("Path to Mylib images file to be read when converting from Mylib (default: input file with suffix -images.txt)").
+            takes_value(true).
+            required(false)).
+        arg(Arg::with_name("COVER_TARGET_DIR").
+            short("ct").
+            long("cover-target").
+            help(super::with_default("Path to directory where cover images are imported", mylib::DEFAULT_COVER_DIRECTORY)).
+            takes_value(true).
+            required(false)).
+        arg(Arg::with_name("INFER_FROM_ISBN").
+            long("infer-from-isbn").
+            help("Infer the empty language and publisher of the books from their ISBN")).
+        arg(super::dialect_arg()).
+        args(&super::cover_args()).
+        arg(super::report_arg())
+}
+
+pub fn run(matches: &ArgMatches) -> Status {
+    let input = matches.value_of("INPUT_FILE").expect("Missing input");
+    let (from, to) = matches.value_of("DIRECTION").
+        and_then(|d| d.split_once('-')).unwrap_or((CODEX, MYLIB));
+
+    let mut report = Report::default();
+
+    convert(matches, input, from, to, &mut report);
+
+    super::finish(matches, report)
+}
+
+/// Returns the path of an output file: either given with the `arg`,
+/// or named after the input file (with the suffix) in the output directory.
+fn out_path(
+    matches: &ArgMatches,
+    arg: &str,
+    input: &str,
+    suffix: &str,
+) -> PathBuf {
+    matches.value_of(arg).map_or_else(
+        || {
+            let basename = if input == STDIO {
+                STDIN_BASENAME
+            } else {
+                Path::new(input).file_stem().
+                    and_then(|os| os.to_str()).unwrap_or(STDIN_BASENAME)
+            };
+
+            Path::new(matches.value_of("OUTPUT_DIR").unwrap_or(".")).
+                join(format!("{}{}", basename, suffix))
+        },
+        PathBuf::from)
+}
+
+fn convert(
+    matches: &ArgMatches,
+    input: &str,
+    from: &str,
+    to: &str,
+    report: &mut Report,
+) {
+    let res = super::mylib_dialect(matches).and_then(
+        |dialect| source(matches, input, from, &dialect).
+            map(|s| (dialect, s)));
+
+    let (dialect, source) = match res {
+        Ok(r) => r,
+        Err(cause) => return report.fail(Status::Input, cause),
+    };
+
+    let mut pipeline = Pipeline::new(source);
+
+    if matches.is_present("INFER_FROM_ISBN") {
+        let mut inference = codex::util::IsbnInference::new();
+
+        pipeline = pipeline.map(move |b| inference.complete(b));
+    }
+
+    if to == MYLIB {
+        pipeline = match super::cover_fetcher(matches, input) {
+            Ok(f) => pipeline.covers(
+                mylib::cover_request, Arc::new(f), super::cover_jobs(matches)),
+            Err(cause) => return report.fail(Status::Input, cause),
+        };
+    }
+
+    let pipeline = match sink(matches, input, to, dialect) {
+        Ok(s) => pipeline.sink(s),
+        Err(cause) => return report.fail(Status::Output, cause),
+    };
+
+    let mut record = 0;
+
+    pipeline.run(|event| match event {
+        // A Codex export cannot be read after an error
+        Event::Invalid(cause) if from == CODEX => report.fail(
+            Status::Input, format!("Fails to parse '{}': {}", input, cause)),
+
+        Event::Invalid(cause) => {
+            record += 1;
+            report.skip(&format!("record #{}", record), cause)
+        }
+
+        Event::Read(entry) => {
+            record += 1;
+            report.read(&entry.book);
+            report.warnings(entry.warnings.clone());
+        }
+
+        Event::Written { entry, issue, .. } => {
+            report.written();
+
+            if entry.cover.is_some() {
+                report.covers.record(&entry.book, issue);
+            }
+        }
+
+        Event::Skipped { entry, cause, .. } =>
+            report.skip(&entry.book.title, cause),
+
+        Event::Unfinished { cause, .. } => report.fail(
+            Status::Output, format!("Fails to write the output: {}", cause)),
+    });
+
+    if to == MYLIB {
+        report.covers.print();
+    }
+}
+
+/// Returns the source of the books in the input, with the given format.
+fn source(
+    matches: &ArgMatches,
+    input: &str,
+    from: &str,
+    dialect: &Dialect,
+) -> Result<Box<dyn BookSource>> {
+    match from {
+        MYLIB => {
+            let img_path = matches.value_of("IMAGES_FILE").map(PathBuf::from).
+                or_else(|| if input == STDIO {
+                    None
+                } else {
+                    let mut p =
+                        Path::new(input).with_extension("").into_os_string();
+
+                    p.push("-images.txt");
+
+                    Some(PathBuf::from(p))
+                });
+
+            let inf = super::open_input(input)?;
+
+            log::info!(target: "cli", "Will read images from '{}'", img_path.as_ref().
+                       map_or_else(|| "".to_string(),
+                                   |p| p.display().to_string()));
+
+            let imf: Box<dyn std::io::Read> =
+                match img_path.as_ref().map(super::open_input) {
+                    Some(Ok(imf)) => imf,
+
+                    Some(Err(cause)) => {
+                        log::warn!(target: "cli", "{}", cause);
+
+                        Box::new(std::io::empty())
+                    }
+
+                    None => Box::new(std::io::empty()),
+                };
+
+            mylib::read(BufReader::new(inf), BufReader::new(imf), dialect).
+                map(|books| Box::new(Books(books)) as Box<dyn BookSource>)
+        }
+
+        GOODREADS => super::open_input(input).
+            and_then(|inf| goodreads::read(BufReader::new(inf))).
+            map(|books| Box::new(Books(books)) as Box<dyn BookSource>),
+
+        CALIBRE => calibre::read(input).
+            map(|books| Box::new(Books(books)) as Box<dyn BookSource>),
+
+        EPUB => epub::read(PathBuf::from(input)).
+            map(|books| Box::new(Books(books)) as Box<dyn BookSource>),
+
+        _ => super::open_codex(matches, input).
+            map(|books| Box::new(books) as Box<dyn BookSource>),
+    }
+}
+
+/// Returns the sink writing the books with the given format,
+/// to the output files named after the input (see `out_path`).
+fn sink(
+    matches: &ArgMatches,
+    input: &str,
+    to: &str,
+    dialect: Dialect,
+) -> Result<Box<dyn BookSink>> {
+    match to {
+        MYLIB => {
+            let csv_path = out_path(matches, "CSV_OUT", input, "-mylib.csv");
+            let img_path =
+                out_path(matches, "IMAGES_OUT", input, "-mylib-images.txt");
+
+            if csv_path == Path::new(STDIO) && img_path == Path::new(STDIO) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "CSV and images cannot be both written to the standard output"));
+            }
+
+            log::info!(target: "cli", "Will write CSV to '{}' and images to '{}'",
+                       csv_path.display(), img_path.display());
+
+            let out = super::create_output(&csv_path)?;
+            let imf = super::create_output(&img_path)?;
+            let cover_dir = matches.value_of("COVER_TARGET_DIR").
+                unwrap_or(mylib::DEFAULT_COVER_DIRECTORY);
+
+            mylib::MylibSink::new(
+                BufWriter::new(out), BufWriter::new(imf), dialect, cover_dir).
+                map(|s| Box::new(s) as Box<dyn BookSink>)
+        }
+
+        GOODREADS => {
+            let csv_path =
+                out_path(matches, "CSV_OUT", input, "-goodreads.csv");
+
+            log::info!(target: "cli", "Will write CSV to '{}'", csv_path.display());
+
+            super::create_output(&csv_path).
+                and_then(|of| goodreads::GoodreadsSink::new(BufWriter::new(of))).
+                map(|s| Box::new(s) as Box<dyn BookSink>)
+        }
+
+        _ => {
+            let xml_path = out_path(matches, "XML_OUT", input, "-codex.xml");
+
+            log::info!(target: "cli", "Will write XML to '{}'", xml_path.display());
+
+            super::create_output(&xml_path).
+                and_then(|of| codex::util::BookWriter::new(BufWriter::new(of))).
+                map(|w| Box::new(w) as Box<dyn BookSink>)
+        }
+    }
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn out_paths(args: &[&str]) -> (PathBuf, PathBuf) {
+        let app = App::new("test").subcommand(command());
+        let matches = app.get_matches_from(
+            [ "test", "convert" ].iter().chain(args.iter()));
+
+        let m = matches.subcommand_matches("convert").unwrap();
+        let input = m.value_of("INPUT_FILE").unwrap();
+
+        (out_path(m, "CSV_OUT", input, "-mylib.csv"),
+         out_path(m, "IMAGES_OUT", input, "-mylib-images.txt"))
+    }
+
+    #[test]
+    fn test_out_path() {
+        assert_eq!(out_paths(&[ "-i", "/backup/lib.xml", "-o", "/tmp/out" ]),
+                   (PathBuf::from("/tmp/out/lib-mylib.csv"),
+                    PathBuf::from("/tmp/out/lib-mylib-images.txt")));
+
+        assert_eq!(out_paths(&[ "-i", "lib.xml", "-o", "/tmp/out/" ]).0,
+                   PathBuf::from("/tmp/out/lib-mylib.csv"));
+
+        assert_eq!(out_paths(&[ "-i", "-" ]).0,
+                   PathBuf::from("./books-mylib.csv"));
+
+        assert_eq!(out_paths(&[ "-i", "-", "--csv-out", "-",
+                                "--images-out", "/tmp/img.txt" ]),
+                   (PathBuf::from("-"), PathBuf::from("/tmp/img.txt")));
+    }
+}
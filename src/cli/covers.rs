@@ -0,0 +1,65 @@
+use clap::{App, ArgMatches, SubCommand};
+
+use codex_mylib::cover;
+use codex_mylib::mylib;
+
+use super::report::{Report, Status};
+
+pub fn command<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("covers").
+        about("Fetches the covers of the Codex XML books into the cache, without converting").
+        arg(super::input_arg("Path to Codex XML file, - for the standard input")).
+        args(&super::codex_args()).
+        args(&super::cover_args()).
+        arg(super::report_arg())
+}
+
+pub fn run(matches: &ArgMatches) -> Status {
+    let input = matches.value_of("INPUT_FILE").expect("Missing input");
+    let mut report = Report::default();
+
+    let res = super::open_codex(matches, input).and_then(
+        |books| super::cover_fetcher(matches, input).map(|f| (books, f)));
+
+    let (books, covers) = match res {
+        Ok(r) => r,
+
+        Err(cause) => {
+            report.fail(Status::Input, format!(
+                "Fails to fetch covers from '{}': {}", input, cause));
+
+            return super::finish(matches, report);
+        }
+    };
+
+    let warnings = books.warnings();
+
+    let books = cover::pool::prefetch(
+        books,
+        |res| res.as_ref().ok().and_then(mylib::cover_request),
+        std::sync::Arc::new(covers),
+        super::cover_jobs(matches));
+
+    for (res, cover) in books {
+        match (res, cover) {
+            (Ok(book), Some(fetched)) => {
+                report.read(&book);
+                report.covers.record(&book, fetched.err())
+            }
+
+            (Ok(book), None) => report.read(&book),
+
+            (Err(cause), _) => {
+                report.fail(Status::Input, format!(
+                    "Fails to parse '{}': {}", input, cause));
+
+                break;
+            }
+        }
+    }
+
+    report.warnings(warnings.take());
+    report.covers.print();
+
+    super::finish(matches, report)
+}
@@ -0,0 +1,140 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use codex_mylib::codex::Book;
+use codex_mylib::model::Isbn;
+use codex_mylib::mylib;
+
+use super::report::Status;
+
+pub fn command<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("inspect").
+        about("Prints the parsed record of the Codex XML books matching an ISBN or a title").
+        arg(super::input_arg("Path to Codex XML file, - for the standard input")).
+        args(&super::codex_args()).
+        arg(Arg::with_name("QUERY").
+            help("ISBN (10 or 13 digits) or part of the title").
+            required(true)).
+        arg(super::dialect_arg())
+}
+
+pub fn run(matches: &ArgMatches) -> Status {
+    let input = matches.value_of("INPUT_FILE").expect("Missing input");
+    let query = Query::new(matches.value_of("QUERY").expect("Missing query"));
+
+    let dialect = match super::mylib_dialect(matches) {
+        Ok(d) => d,
+
+        Err(cause) => {
+            log::error!(target: "cli", "{}", cause);
+
+            return Status::Input;
+        }
+    };
+
+    let reader = match super::open_codex(matches, input) {
+        Ok(r) => r,
+
+        Err(cause) => {
+            log::error!(target: "cli", "Fails to read '{}': {}", input, cause);
+
+            return Status::Input;
+        }
+    };
+
+    let warnings = reader.warnings();
+    let mut found = 0;
+    let mut status = Status::Success;
+
+    for res in reader {
+        let book = match res {
+            Ok(b) => b,
+
+            Err(cause) => {
+                log::error!(target: "cli", "Fails to parse '{}': {}", input, cause);
+                status = Status::Input;
+
+                break;
+            }
+        };
+
+        let book_warnings = warnings.take();
+
+        if !query.matches(&book) {
+            continue;
+        }
+
+        found += 1;
+
+        println!("{:#?}", book);
+
+        for w in book_warnings {
+            println!("Warning: {}:{}", input, w);
+        }
+
+        let mut cw = dialect.csv_writer(Vec::<u8>::new());
+        let row = mylib::write(
+            &mut cw, &mut std::io::sink(), &book, &dialect,
+            mylib::DEFAULT_COVER_DIRECTORY, None).
+            and_then(|_| cw.into_inner().map_err(
+                |e| std::io::Error::other(e.to_string())));
+
+        match row {
+            Ok(r) => print!("Mylib: {}", dialect.decode(&r)),
+            Err(cause) => log::error!(target: "cli", "Fails to write as Mylib: {}", cause),
+        }
+    }
+
+    if found == 0 {
+        println!("No book matching '{}'", matches.value_of("QUERY").unwrap_or(""));
+    }
+
+    status
+}
+
+enum Query {
+    Isbn(String),
+    /// Part of the title, lowercase
+    Title(String),
+}
+
+impl Query {
+    fn new(query: &str) -> Query {
+        match Isbn::parse(query) {
+            Ok(isbn) => Query::Isbn(isbn13(&isbn)),
+            Err(_) => Query::Title(query.to_lowercase()),
+        }
+    }
+
+    fn matches(&self, book: &Book) -> bool {
+        match self {
+            Query::Isbn(digits) => book.isbn.iter().any(|i| &isbn13(i) == digits),
+            Query::Title(part) => book.title.to_lowercase().contains(part),
+        }
+    }
+}
+
+fn isbn13(isbn: &Isbn) -> String {
+    isbn.to_isbn13().unwrap_or_else(|| isbn.clone()).digits()
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query() {
+        let mut book = codex_mylib::codex::empty_book();
+
+        book.title = "Accros du roc".to_string();
+        book.isbn = vec![ Isbn::Isbn13(9782266211963) ];
+
+        assert!(Query::new("978-2-266-21196-3").matches(&book));
+        assert!(Query::new("226621196X").matches(&book));
+        assert!(Query::new("ACCROS").matches(&book));
+
+        assert!(!Query::new("9782266211964").matches(&book));
+        assert!(!Query::new("Ally").matches(&book));
+    }
+}
@@ -0,0 +1,227 @@
+use std::io::Write;
+use std::str::FromStr;
+
+use clap::{Arg, ArgMatches};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+const FORMAT_TEXT: &str = "text";
+const FORMAT_JSON: &str = "json";
+
+/// Name of the crate, prefixing the targets of the untargeted log records
+const CRATE_PREFIX: &str = concat!(env!("CARGO_CRATE_NAME"), "::");
+
+/// Returns the logging arguments, global to all the subcommands.
+pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("VERBOSE").
+            short("v").
+            long("verbose").
+            help("Logs more details: -v for the information, -vv for debugging").
+            multiple(true).
+            global(true),
+        Arg::with_name("QUIET").
+            short("q").
+            long("quiet").
+            help("Only logs the errors").
+            conflicts_with("VERBOSE").
+            global(true),
+        Arg::with_name("LOG_FILTER").
+            long("log-filter").
+            help("Log levels by target (cli, xml, mylib, goodreads, opf, calibre, epub), e.g. warn,mylib=info,xml=off").
+            env("CODEX_MYLIB_LOG").
+            takes_value(true).
+            validator(|spec| Filter::parse(&spec, LevelFilter::Warn).map(|_| ())).
+            global(true),
+        Arg::with_name("LOG_FORMAT").
+            long("log-format").
+            help("Format of the log lines, written to the standard error").
+            takes_value(true).
+            possible_values(&[FORMAT_TEXT, FORMAT_JSON]).
+            default_value(FORMAT_TEXT).
+            global(true),
+    ]
+}
+
+/// Installs the logger according the arguments (see `args`).
+pub fn init(matches: &ArgMatches) {
+    let default = if matches.is_present("QUIET") {
+        LevelFilter::Error
+    } else {
+        match matches.occurrences_of("VERBOSE") {
+            0 => LevelFilter::Warn,
+            1 => LevelFilter::Info,
+            2 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        }
+    };
+
+    let filter = matches.value_of("LOG_FILTER").
+        and_then(|spec| Filter::parse(spec, default).ok()).
+        unwrap_or_else(|| Filter::new(default));
+
+    let logger = Logger {
+        filter,
+        json: matches.value_of("LOG_FORMAT") == Some(FORMAT_JSON),
+    };
+
+    log::set_max_level(logger.filter.max_level());
+
+    if let Err(cause) = log::set_logger(Box::leak(Box::new(logger))) {
+        eprintln!("Fails to initialize logging: {}", cause);
+    }
+}
+
+// ---
+
+/// Log levels by target
+#[derive(Debug, PartialEq)]
+struct Filter {
+    default: LevelFilter,
+    /// Target and level, the most specific first
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    fn new(default: LevelFilter) -> Filter {
+        Filter { default, targets: vec![] }
+    }
+
+    /// Parses comma separated directives, either `level` (overriding
+    /// the `default` one) or `target=level`.
+    fn parse(spec: &str, default: LevelFilter) -> Result<Filter, String> {
+        let level = |s: &str| LevelFilter::from_str(s.trim()).
+            map_err(|_| format!("Invalid log level '{}'", s.trim()));
+
+        let mut filter = Filter::new(default);
+
+        for directive in spec.split(',').filter(|d| !d.trim().is_empty()) {
+            match directive.find('=') {
+                Some(i) => filter.targets.push((
+                    directive[..i].trim().to_string(),
+                    level(&directive[i + 1..])?)),
+
+                None => filter.default = level(directive)?,
+            }
+        }
+
+        filter.targets.sort_by_key(|(t, _)| std::cmp::Reverse(t.len()));
+
+        Ok(filter)
+    }
+
+    fn level(&self, target: &str) -> LevelFilter {
+        let target = target.trim_start_matches(CRATE_PREFIX);
+
+        self.targets.iter().
+            find(|(t, _)| target == t ||
+                 (target.starts_with(t.as_str()) &&
+                  target[t.len()..].starts_with("::"))).
+            map_or(self.default, |(_, l)| *l)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.targets.iter().map(|(_, l)| *l).
+            fold(self.default, std::cmp::max)
+    }
+}
+
+// ---
+
+struct Logger {
+    filter: Filter,
+    /// Whether to write JSON lines
+    json: bool,
+}
+
+impl Logger {
+    fn format(&self, record: &Record) -> String {
+        let target = record.target().trim_start_matches(CRATE_PREFIX);
+
+        if self.json {
+            let now = time::OffsetDateTime::now_utc();
+            let time = format!("{}T{:02}:{:02}:{:02}Z", now.date(),
+                               now.hour(), now.minute(), now.second());
+
+            json::object!{
+                time: time,
+                level: record.level().to_string(),
+                target: target,
+                message: record.args().to_string(),
+            }.dump()
+        } else {
+            format!("[{} {}] {}", record.level(), target, record.args())
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let line = self.format(record);
+
+            let _ = writeln!(std::io::stderr(), "{}", line);
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use log::Level;
+
+    #[test]
+    fn test_filter() {
+        let filter = Filter::parse(
+            "mylib=info, xml=off,cover::normalize=debug", LevelFilter::Warn).
+            unwrap();
+
+        assert_eq!(filter.level("mylib"), LevelFilter::Info);
+        assert_eq!(filter.level("xml"), LevelFilter::Off);
+        assert_eq!(filter.level("cli"), LevelFilter::Warn);
+        assert_eq!(filter.level("xmlish"), LevelFilter::Warn);
+        assert_eq!(filter.level(&format!("{}model::range", CRATE_PREFIX)),
+                   LevelFilter::Warn);
+        assert_eq!(filter.level(&format!("{}cover::normalize", CRATE_PREFIX)),
+                   LevelFilter::Debug);
+        assert_eq!(filter.max_level(), LevelFilter::Debug);
+
+        assert_eq!(Filter::parse("error,mylib=warn", LevelFilter::Info).
+                   map(|f| f.default), Ok(LevelFilter::Error));
+
+        assert_eq!(Filter::parse("mylib=loud", LevelFilter::Warn),
+                   Err("Invalid log level 'loud'".to_string()));
+    }
+
+    #[test]
+    fn test_format() {
+        let logger = |json| Logger { filter: Filter::new(LevelFilter::Warn), json };
+
+        let format = |logger: Logger| logger.format(&Record::builder().
+            level(Level::Warn).
+            target("mylib").
+            args(format_args!("Invalid ISBN13 \"{}\"", "978-2")).
+            build());
+
+        assert_eq!(format(logger(false)),
+                   "[WARN mylib] Invalid ISBN13 \"978-2\"");
+
+        let line = json::parse(&format(logger(true))).unwrap();
+
+        assert_eq!(line["level"], "WARN");
+        assert_eq!(line["target"], "mylib");
+        assert_eq!(line["message"], "Invalid ISBN13 \"978-2\"");
+        assert!(line["time"].as_str().is_some());
+    }
+}
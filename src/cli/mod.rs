@@ -0,0 +1,269 @@
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufReader, Error, Read, Result, Write};
+use std::path::Path;
+
+use clap::{Arg, ArgMatches};
+
+use reqwest::blocking::Client;
+
+use codex_mylib::codex::util::BookReader;
+use codex_mylib::cover;
+use codex_mylib::mylib::dialect::Dialect;
+
+use report::{Report, Status};
+
+pub mod convert;
+pub mod covers;
+pub mod inspect;
+pub mod logging;
+pub mod report;
+pub mod stats;
+pub mod validate;
+
+/// Path standing for the standard input or output
+pub const STDIO: &str = "-";
+
+const DEFAULT_COVER_JOBS: usize = 4;
+const DEFAULT_COVER_RATE: u32 = 5;
+
+pub fn validate_number(value: String) -> std::result::Result<(), String> {
+    value.parse::<u32>().map(|_| ()).map_err(|cause| cause.to_string())
+}
+
+/// Returns the help message with the default value
+/// (kept for the whole process, as the CLI arguments).
+fn with_default<D: Display>(help: &str, default: D) -> &'static str {
+    Box::leak(format!("{} (default: {})", help, default).into_boxed_str())
+}
+
+pub fn input_arg<'a, 'b>(help: &'b str) -> Arg<'a, 'b> {
+    Arg::with_name("INPUT_FILE").
+        short("i").
+        long("input").
+        help(help).
+        takes_value(true).
+        required(true)
+}
+
+pub fn report_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("REPORT").
+        long("report").
+        help("Path to JSON file where to write the run report, - for the standard output").
+        takes_value(true).
+        required(false)
+}
+
+/// Writes the report if required (see `report_arg`),
+/// and returns the final status.
+pub fn finish(matches: &ArgMatches, mut report: Report) -> Status {
+    if let Some(path) = matches.value_of("REPORT") {
+        if let Err(cause) = report.write(path) {
+            report.fail(Status::Output, format!(
+                "Fails to write report '{}': {}", path, cause));
+        }
+    }
+
+    report.status()
+}
+
+/// Opens the input file, or the standard input for `-`.
+pub fn open_input<P: AsRef<Path>>(path: P) -> Result<Box<dyn Read>> {
+    let p = path.as_ref();
+
+    if p == Path::new(STDIO) {
+        Ok(Box::new(std::io::stdin()))
+    } else {
+        File::open(p).map(|f| Box::new(f) as Box<dyn Read>).map_err(
+            |cause| Error::new(cause.kind(), format!(
+                "Fails to open '{}': {}", p.display(), cause)))
+    }
+}
+
+/// Creates the output file, or uses the standard output for `-`.
+pub fn create_output<P: AsRef<Path>>(path: P) -> Result<Box<dyn Write>> {
+    let p = path.as_ref();
+
+    if p == Path::new(STDIO) {
+        Ok(Box::new(std::io::stdout()))
+    } else {
+        File::create(p).map(|f| Box::new(f) as Box<dyn Write>).map_err(
+            |cause| Error::new(cause.kind(), format!(
+                "Fails to create '{}': {}", p.display(), cause)))
+    }
+}
+
+/// Arguments to validate the Codex XML input (see `open_codex`).
+pub fn codex_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("STRICT").
+            long("strict").
+            help("Fails on the first invalid field of the Codex XML books (invalid pageCount, publishDate, unknown identifier type, ...)").
+            required(false),
+        Arg::with_name("MAX_ERRORS").
+            long("max-errors").
+            help("Fails once more than this number of invalid fields are found in the Codex XML books").
+            takes_value(true).
+            validator(validate_number).
+            conflicts_with("STRICT").
+            required(false),
+    ]
+}
+
+/// Opens a reader of the Codex XML books, either lenient or strict
+/// according the arguments (see `codex_args`).
+pub fn open_codex(
+    matches: &ArgMatches,
+    input: &str,
+) -> Result<BookReader<BufReader<Box<dyn Read>>>> {
+    log::info!(target: "cli", "Input file = {}", input);
+
+    let max_errors = matches.value_of("MAX_ERRORS").
+        and_then(|v| v.parse().ok());
+
+    open_input(input).map(|r| {
+        let reader = codex_mylib::codex::util::parse(BufReader::new(r));
+
+        match max_errors {
+            _ if matches.is_present("STRICT") => reader.strict(),
+            Some(max) => reader.max_errors(max),
+            None => reader,
+        }
+    })
+}
+
+pub fn dialect_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("MYLIB_DIALECT").
+        long("mylib-dialect").
+        help("Path to TOML file defining the Mylib CSV layout: columns, delimiter, quoting, encoding, header, line ending (default: resources/mylib-default.toml)").
+        takes_value(true).
+        required(false)
+}
+
+/// Returns the Mylib dialect given with `dialect_arg`, or the default one.
+pub fn mylib_dialect(matches: &ArgMatches) -> Result<Dialect> {
+    matches.value_of("MYLIB_DIALECT").map_or_else(
+        || Ok(Dialect::default()), Dialect::load)
+}
+
+/// Arguments to fetch the covers (see `cover_fetcher`).
+pub fn cover_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("COVER_CACHE_DIR").
+            long("cover-cache").
+            help("Path to directory where cover images are cached (default: ~/.cache/codex-mylib/covers)").
+            takes_value(true).
+            required(false),
+        Arg::with_name("OFFLINE").
+            long("offline").
+            help("Use only the cached cover images").
+            conflicts_with("REFRESH_COVERS"),
+        Arg::with_name("REFRESH_COVERS").
+            long("refresh-covers").
+            help("Revalidate the cached cover images"),
+        Arg::with_name("COVER_JOBS").
+            long("cover-jobs").
+            help(with_default("Maximum number of cover images downloaded concurrently", DEFAULT_COVER_JOBS)).
+            takes_value(true).
+            validator(validate_number).
+            required(false),
+        Arg::with_name("COVER_RATE").
+            long("cover-rate").
+            help(with_default("Maximum number of cover requests per second to a same host, 0 for unlimited", DEFAULT_COVER_RATE)).
+            takes_value(true).
+            validator(validate_number).
+            required(false),
+        Arg::with_name("COVER_MAX_SIZE").
+            long("cover-max-size").
+            help(with_default("Maximum width or height in pixels of the cover images, larger ones being downscaled", cover::normalize::DEFAULT_MAX_DIMENSION)).
+            takes_value(true).
+            validator(validate_number).
+            required(false),
+        Arg::with_name("COVER_PLACEHOLDERS").
+            long("cover-placeholders").
+            help("Path to directory of placeholder images to be skipped as covers, besides the known ones").
+            takes_value(true).
+            required(false),
+        Arg::with_name("COVER_RETRIES").
+            long("cover-retries").
+            help(with_default("Maximum number of retries for a failed cover request", cover::retry::DEFAULT_MAX_ATTEMPTS - 1)).
+            takes_value(true).
+            validator(validate_number).
+            required(false),
+        Arg::with_name("COVER_BACKOFF").
+            long("cover-backoff").
+            help(with_default("Delay in milliseconds before the first retry, doubled for each next one", cover::retry::DEFAULT_BASE_DELAY.as_millis())).
+            takes_value(true).
+            validator(validate_number).
+            required(false),
+    ]
+}
+
+/// Returns the number of concurrent cover downloads.
+pub fn cover_jobs(matches: &ArgMatches) -> usize {
+    matches.value_of("COVER_JOBS").
+        and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_COVER_JOBS)
+}
+
+pub fn cover_fetcher(
+    matches: &ArgMatches,
+    input: &str,
+) -> Result<cover::CoverFetcher> {
+    let mode = if matches.is_present("OFFLINE") {
+        cover::CacheMode::Offline
+    } else if matches.is_present("REFRESH_COVERS") {
+        cover::CacheMode::Refresh
+    } else {
+        cover::CacheMode::Cached
+    };
+
+    let cache_dir = matches.value_of("COVER_CACHE_DIR").
+        map(std::path::PathBuf::from).
+        or_else(cover::cache::CoverCache::default_dir);
+
+    let cache = cache_dir.and_then(|dir| {
+        cover::cache::CoverCache::open(&dir).map_or_else(
+            |cause| {
+                log::warn!(target: "cli", "Fails to open cover cache '{}': {}",
+                           dir.display(), cause);
+
+                None
+            },
+            Some)
+    });
+
+    let rate = matches.value_of("COVER_RATE").
+        and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_COVER_RATE);
+
+    let retry = cover::retry::RetryPolicy {
+        max_attempts: matches.value_of("COVER_RETRIES").
+            and_then(|v| v.parse::<u32>().ok()).
+            map_or(cover::retry::DEFAULT_MAX_ATTEMPTS, |n| n + 1),
+        base_delay: matches.value_of("COVER_BACKOFF").
+            and_then(|v| v.parse().ok()).
+            map_or(cover::retry::DEFAULT_BASE_DELAY,
+                   std::time::Duration::from_millis),
+        ..Default::default()
+    };
+
+    let mut placeholders = cover::placeholder::PlaceholderSet::bundled();
+
+    if let Some(dir) = matches.value_of("COVER_PLACEHOLDERS") {
+        placeholders.add_dir(dir)?;
+    }
+
+    let normalizer = cover::normalize::Normalizer {
+        max_dimension: matches.value_of("COVER_MAX_SIZE").
+            and_then(|v| v.parse().ok()).
+            unwrap_or(cover::normalize::DEFAULT_MAX_DIMENSION),
+        placeholders,
+        ..Default::default()
+    };
+
+    Client::builder().
+        timeout(std::time::Duration::from_secs(30)).build().
+        map_err(Error::other).
+        map(|http| cover::CoverFetcher::new(http, cache, mode).
+            rate_limited(rate).with_retry(retry).normalized(normalizer).
+            relative_to(Path::new(input).parent().unwrap_or(Path::new("."))))
+}
@@ -0,0 +1,294 @@
+use std::fmt::Display;
+use std::io::{Error, ErrorKind, Result, Write};
+
+use json::JsonValue;
+
+use codex_mylib::codex::{Book, Warning};
+use codex_mylib::cover;
+
+/// Outcome of a command, as process exit status
+/// (ordered by severity).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    Success,
+    /// Some books or covers could not be converted
+    Partial,
+    /// No cover could be fetched, because of network errors
+    Network,
+    /// The input cannot be read, or is invalid
+    Input,
+    /// The output cannot be written
+    Output,
+}
+
+impl Status {
+    pub fn code(self) -> i32 {
+        match self {
+            Status::Success => 0,
+            Status::Input => 2,
+            Status::Output => 3,
+            Status::Network => 4,
+            Status::Partial => 5,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Status::Success => "success",
+            Status::Partial => "partial",
+            Status::Network => "network",
+            Status::Input => "input",
+            Status::Output => "output",
+        }
+    }
+}
+
+// ---
+
+/// Outcome of the cover requests, summarized at the end of a run
+#[derive(Default)]
+pub struct CoverReport {
+    fetched: usize,
+    placeholders: usize,
+    /// Title, cover URL and cause
+    failures: Vec<(String, String, Error)>,
+}
+
+impl CoverReport {
+    /// Records the outcome of the cover request for the book.
+    pub fn record(&mut self, book: &Book, failure: Option<Error>) {
+        match failure {
+            Some(cause) if cover::placeholder::is_placeholder(&cause) =>
+                self.placeholders += 1,
+
+            Some(cause) => self.failures.push(
+                (book.title.to_string(), book.cover.to_string(), cause)),
+
+            None => self.fetched += 1,
+        }
+    }
+
+    /// Returns `Network` if all the covers failed with network errors,
+    /// or `Partial` if some covers failed.
+    fn status(&self) -> Status {
+        let network = |e: &Error| matches!(
+            e.kind(),
+            ErrorKind::Interrupted | ErrorKind::TimedOut |
+            ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset);
+
+        if self.failures.is_empty() {
+            Status::Success
+        } else if self.fetched == 0 &&
+            self.failures.iter().all(|(_, _, e)| network(e)) {
+            Status::Network
+        } else {
+            Status::Partial
+        }
+    }
+
+    /// Logs the report as warnings, so that it is shown by default
+    /// (on the standard error, the standard output possibly being used for
+    /// the converted books).
+    pub fn print(&self) {
+        log::warn!(target: "cli", "{} cover(s) fetched", self.fetched);
+
+        if self.placeholders > 0 {
+            log::warn!(target: "cli", "{} placeholder cover(s) skipped",
+                       self.placeholders);
+        }
+
+        if !self.failures.is_empty() {
+            log::warn!(target: "cli", "{} cover(s) could not be fetched",
+                       self.failures.len());
+
+            for (title, url, cause) in &self.failures {
+                log::warn!(target: "cli", "Cover of '{}' could not be fetched ({}): {}",
+                           title, url, cause);
+            }
+        }
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let failures: Vec<JsonValue> = self.failures.iter().
+            map(|(title, url, cause)| json::object!{
+                book: title.as_str(),
+                url: url.as_str(),
+                error: cause.to_string(),
+            }).collect();
+
+        json::object!{
+            fetched: self.fetched,
+            placeholders: self.placeholders,
+            failed: self.failures.len(),
+            failures: failures,
+        }
+    }
+}
+
+// ---
+
+/// Summary of a run, as written with `--report`
+#[derive(Default)]
+pub struct Report {
+    /// Titles of the books read, in the input order
+    read: Vec<String>,
+    written: usize,
+    /// Title and cause
+    skipped: Vec<(String, String)>,
+    warnings: Vec<Warning>,
+    pub covers: CoverReport,
+    /// Failures of the whole run
+    errors: Vec<(Status, String)>,
+}
+
+impl Report {
+    pub fn read(&mut self, book: &Book) {
+        self.read.push(book.title.to_string());
+    }
+
+    pub fn written(&mut self) {
+        self.written += 1;
+    }
+
+    /// Records a book that cannot be converted.
+    pub fn skip<E: Display>(&mut self, title: &str, cause: E) {
+        log::warn!(target: "cli", "Skip book '{}': {}", title, cause);
+
+        self.skipped.push((title.to_string(), cause.to_string()));
+    }
+
+    pub fn warnings(&mut self, warnings: Vec<Warning>) {
+        self.warnings.extend(warnings);
+    }
+
+    /// Records (and logs) a failure of the run.
+    pub fn fail<E: Display>(&mut self, status: Status, cause: E) {
+        log::error!(target: "cli", "{}", cause);
+
+        self.errors.push((status, cause.to_string()));
+    }
+
+    pub fn status(&self) -> Status {
+        let partial = if self.skipped.is_empty() {
+            Status::Success
+        } else {
+            Status::Partial
+        };
+
+        self.errors.iter().map(|(s, _)| *s).
+            chain(vec![ partial, self.covers.status() ]).
+            max().unwrap_or(Status::Success)
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let title = |index: usize| self.read.get(index).
+            map_or_else(|| "".to_string(), |t| t.to_string());
+
+        let warnings: Vec<JsonValue> = self.warnings.iter().
+            map(|w| json::object!{
+                book: title(w.book),
+                line: w.line,
+                column: w.column,
+                message: w.message.as_str(),
+            }).collect();
+
+        let skipped: Vec<JsonValue> = self.skipped.iter().
+            map(|(book, cause)| json::object!{
+                book: book.as_str(),
+                error: cause.as_str(),
+            }).collect();
+
+        let errors: Vec<JsonValue> = self.errors.iter().
+            map(|(_, cause)| cause.as_str().into()).collect();
+
+        let status = self.status();
+
+        json::object!{
+            status: status.name(),
+            exitCode: status.code(),
+            books: json::object!{
+                read: self.read.len(),
+                written: self.written,
+                skipped: self.skipped.len(),
+            },
+            covers: self.covers.to_json(),
+            warnings: warnings,
+            skipped: skipped,
+            errors: errors,
+        }
+    }
+
+    /// Writes the report as JSON to the given path (`-` for stdout).
+    pub fn write(&self, path: &str) -> Result<()> {
+        super::create_output(path).and_then(|mut w| {
+            self.to_json().write_pretty(&mut w, 2).
+                and_then(|_| w.write_all(b"\n"))
+        })
+    }
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(title: &str) -> Book {
+        let mut b = codex_mylib::codex::empty_book();
+
+        b.title = title.to_string();
+        b.cover = format!("http://covers/{}.jpg", title);
+
+        b
+    }
+
+    #[test]
+    fn test_status() {
+        let mut report = Report::default();
+
+        assert_eq!(report.status(), Status::Success);
+
+        report.covers.record(&book("Ally"), Some(Error::new(
+            ErrorKind::Interrupted, "Connection refused")));
+
+        assert_eq!(report.status(), Status::Network);
+
+        report.covers.record(&book("Accros du roc"), None);
+
+        assert_eq!(report.status(), Status::Partial);
+
+        report.fail(Status::Input, "XML error at 3:29: Unexpected token");
+
+        assert_eq!(report.status(), Status::Input);
+        assert_eq!(report.status().code(), 2);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let mut report = Report::default();
+
+        report.read(&book("Accros du roc"));
+        report.written();
+        report.read(&book("Ally"));
+        report.skip("Ally", "Invalid record");
+        report.warnings(vec![ Warning {
+            book: 0,
+            line: 4,
+            column: 16,
+            message: "Invalid pageCount 'many'".to_string(),
+        } ]);
+        report.covers.record(&book("Accros du roc"), None);
+
+        let json = report.to_json();
+
+        assert_eq!(json["status"], "partial");
+        assert_eq!(json["exitCode"], 5);
+        assert_eq!(json["books"]["read"], 2);
+        assert_eq!(json["books"]["written"], 1);
+        assert_eq!(json["books"]["skipped"], 1);
+        assert_eq!(json["covers"]["fetched"], 1);
+        assert_eq!(json["warnings"][0]["book"], "Accros du roc");
+        assert_eq!(json["warnings"][0]["line"], 4);
+        assert_eq!(json["skipped"][0]["error"], "Invalid record");
+    }
+}
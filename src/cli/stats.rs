@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use codex_mylib::codex::Book;
+
+use super::report::Status;
+
+const DEFAULT_TOP: usize = 10;
+
+pub fn command<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("stats").
+        about("Prints statistics about the Codex XML books").
+        arg(super::input_arg("Path to Codex XML file, - for the standard input")).
+        args(&super::codex_args()).
+        arg(Arg::with_name("TOP").
+            long("top").
+            help(super::with_default("Number of most frequent authors, publishers, categories and years", DEFAULT_TOP)).
+            takes_value(true).
+            validator(super::validate_number).
+            required(false))
+}
+
+pub fn run(matches: &ArgMatches) -> Status {
+    let input = matches.value_of("INPUT_FILE").expect("Missing input");
+    let top = matches.value_of("TOP").
+        and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_TOP);
+
+    let reader = match super::open_codex(matches, input) {
+        Ok(r) => r,
+
+        Err(cause) => {
+            log::error!(target: "cli", "Fails to read '{}': {}", input, cause);
+
+            return Status::Input;
+        }
+    };
+
+    let mut stats = Stats::default();
+    let mut status = Status::Success;
+
+    for res in reader {
+        match res {
+            Ok(book) => stats.add(&book),
+
+            Err(cause) => {
+                log::error!(target: "cli", "Fails to parse '{}': {}", input, cause);
+                status = Status::Input;
+
+                break;
+            }
+        }
+    }
+
+    stats.print(top);
+
+    status
+}
+
+/// Counts of the books
+#[derive(Default)]
+struct Stats {
+    books: usize,
+    authors: HashMap<String, usize>,
+    publishers: HashMap<String, usize>,
+    categories: HashMap<String, usize>,
+    years: HashMap<String, usize>,
+    /// Number of books by missing field
+    missing: HashMap<&'static str, usize>,
+}
+
+impl Stats {
+    fn add(&mut self, book: &Book) {
+        self.books += 1;
+
+        for a in &book.authors {
+            *self.authors.entry(a.name.to_string()).or_insert(0) += 1;
+        }
+
+        for k in &book.kind {
+            *self.categories.entry(k.to_string()).or_insert(0) += 1;
+        }
+
+        if !book.publisher.is_empty() {
+            *self.publishers.entry(book.publisher.to_string()).or_insert(0) += 1;
+        }
+
+        if let Some(d) = book.pubdate {
+            *self.years.entry(d.year().to_string()).or_insert(0) += 1;
+        }
+
+        let missing = [
+            ("authors", book.authors.is_empty()),
+            ("publisher", book.publisher.is_empty()),
+            ("publishDate", book.pubdate.is_none()),
+            ("pageCount", book.pages == 0),
+            ("ISBN", book.isbn.is_empty()),
+            ("description", book.summary.is_empty()),
+            ("coverUrl", book.cover.is_empty()),
+        ];
+
+        for (field, _) in missing.iter().filter(|(_, m)| *m) {
+            *self.missing.entry(field).or_insert(0) += 1;
+        }
+    }
+
+    fn print(&self, top: usize) {
+        println!("{} book(s)", self.books);
+
+        print_counts("Authors", &self.authors, top);
+        print_counts("Publishers", &self.publishers, top);
+        print_counts("Categories", &self.categories, top);
+        print_counts("Years", &self.years, top);
+
+        let missing: HashMap<String, usize> = self.missing.iter().
+            map(|(k, v)| (k.to_string(), *v)).collect();
+
+        print_counts("Missing fields", &missing, usize::MAX);
+    }
+}
+
+/// Returns the most frequent values (then sorted by value).
+fn most_frequent(counts: &HashMap<String, usize>, top: usize) -> Vec<(&str, usize)> {
+    let mut sorted: Vec<(&str, usize)> =
+        counts.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    sorted.truncate(top);
+
+    sorted
+}
+
+fn print_counts(title: &str, counts: &HashMap<String, usize>, top: usize) {
+    if counts.is_empty() {
+        return;
+    }
+
+    println!("\n{} ({}):", title, counts.len());
+
+    for (value, count) in most_frequent(counts, top) {
+        println!("  {:>5}  {}", count, value);
+    }
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use codex_mylib::codex::{Author, empty_book};
+
+    #[test]
+    fn test_add() {
+        let author = |name: &str| Author {
+            first_name: "".to_string(),
+            last_name: "".to_string(),
+            name: name.to_string(),
+        };
+
+        let mut b1 = empty_book();
+
+        b1.authors = vec![ author("Terry Pratchett") ];
+        b1.publisher = "Pocket".to_string();
+        b1.pubdate = Some(time::date!(2012-07-10));
+        b1.kind = vec![ "Fantasy".to_string() ];
+
+        let mut b2 = empty_book();
+
+        b2.authors = vec![ author("Terry Pratchett"), author("Neil Gaiman") ];
+        b2.pubdate = Some(time::date!(2012-01-01));
+        b2.kind = vec![ "Fantasy".to_string(), "Humour".to_string() ];
+
+        let mut stats = Stats::default();
+
+        stats.add(&b1);
+        stats.add(&b2);
+
+        assert_eq!(stats.books, 2);
+
+        assert_eq!(most_frequent(&stats.authors, 10), vec![
+            ("Terry Pratchett", 2), ("Neil Gaiman", 1) ]);
+
+        assert_eq!(most_frequent(&stats.categories, 1), vec![ ("Fantasy", 2) ]);
+        assert_eq!(most_frequent(&stats.years, 10), vec![ ("2012", 2) ]);
+
+        assert_eq!(stats.missing.get("publisher"), Some(&1));
+        assert_eq!(stats.missing.get("ISBN"), Some(&2));
+        assert_eq!(stats.missing.get("authors"), None);
+    }
+}
@@ -0,0 +1,56 @@
+use clap::{App, ArgMatches, SubCommand};
+
+use super::report::Status;
+
+pub fn command<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("validate").
+        about("Checks a Codex XML file, reporting the malformed XML and the invalid fields").
+        arg(super::input_arg("Path to Codex XML file, - for the standard input")).
+        args(&super::codex_args())
+}
+
+pub fn run(matches: &ArgMatches) -> Status {
+    let input = matches.value_of("INPUT_FILE").expect("Missing input");
+
+    let reader = match super::open_codex(matches, input) {
+        Ok(r) => r,
+
+        Err(cause) => {
+            log::error!(target: "cli", "Fails to validate '{}': {}", input, cause);
+
+            return Status::Input;
+        }
+    };
+
+    let warnings = reader.warnings();
+    let mut books = 0;
+    let mut invalid = 0;
+
+    for res in reader {
+        match res {
+            Ok(book) => {
+                books += 1;
+
+                for w in warnings.take() {
+                    invalid += 1;
+
+                    println!("{}:{} ({})", input, w, book.title);
+                }
+            }
+
+            Err(cause) => {
+                invalid += 1;
+
+                log::error!(target: "cli", "{}: {}", input, cause);
+            }
+        }
+    }
+
+    println!("{} book(s), {} error(s)", books, invalid);
+
+    if invalid > 0 {
+        Status::Input
+    } else {
+        Status::Success
+    }
+}
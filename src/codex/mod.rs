@@ -2,6 +2,8 @@ use std::option::Option;
 
 use std::fmt::{Display,Formatter};
 
+use std::io::{Error, ErrorKind};
+
 use time::Date;
 
 use super::model::Isbn;
@@ -11,27 +13,61 @@ pub mod util;
 #[derive(Debug)]
 pub struct Book {
     pub title: String,
+    pub subtitle: String,
     pub authors: Vec<Author>,
     pub kind: Vec<String>,
     pub pubdate: Option<Date>,
     pub publisher: String,
     pub pages: u16,
     pub isbn: Vec<Isbn>,
+    /// Identifiers other than the ISBNs, as scheme and value
+    /// (e.g. `("goodreads", "2213")`)
+    pub identifiers: Vec<(String, String)>,
     pub summary: String,
     pub cover: String,
+    pub language: String,
+    pub series: String,
+    /// Number of the book within its series
+    pub volume: Option<u16>,
+    /// Binding (e.g. "Paperback", "Hardcover")
+    pub format: String,
+    /// Personal rating, in stars
+    pub rating: Option<f32>,
+    pub read: bool,
+    /// Date the reading started
+    pub read_start: Option<Date>,
+    /// Date the reading ended
+    pub read_end: Option<Date>,
+    /// Name of the person the book is loaned to
+    pub loaned_to: String,
+    pub notes: String,
+    pub tags: Vec<String>,
 }
 
 pub fn empty_book() -> Book {
     Book {
         title: "".to_string(),
+        subtitle: "".to_string(),
         authors: vec![],
         kind: vec![],
         pubdate: None,
         publisher: "".to_string(),
         pages: 0,
         isbn: vec![],
+        identifiers: vec![],
         summary: "".to_string(),
         cover: "".to_string(),
+        language: "".to_string(),
+        series: "".to_string(),
+        volume: None,
+        format: "".to_string(),
+        rating: None,
+        read: false,
+        read_start: None,
+        read_end: None,
+        loaned_to: "".to_string(),
+        notes: "".to_string(),
+        tags: vec![],
     }
 }
 
@@ -50,50 +86,122 @@ pub fn empty_author() -> Author {
     }
 }
 
+/// Returns the author for the given full name,
+/// considering the first word as the first name.
+pub(crate) fn author_named(name: &str) -> Author {
+    let mut a = empty_author();
+
+    if let Some((first, last)) = name.split_once(' ') {
+        a.first_name = first.to_string();
+        a.last_name = last.to_string();
+    }
+
+    a.name = name.to_string();
+
+    a
+}
+
+/// Returns the author from its sort name (e.g. "Last, First"),
+/// and its full name if known.
+pub(crate) fn author_sorted(sort: &str, name: &str) -> Author {
+    let mut a = empty_author();
+
+    match sort.split_once(',') {
+        Some((last, first)) => {
+            a.first_name = first.trim().to_string();
+            a.last_name = last.trim().to_string();
+        }
+
+        None => a.last_name = sort.trim().to_string(),
+    }
+
+    a.name = if name.is_empty() {
+        format!("{} {}", a.first_name, a.last_name).trim().to_string()
+    } else {
+        name.to_string()
+    };
+
+    a
+}
+
+/// Error raised while reading books from a Codex export,
+/// positioned at the 1-based line and column of the XML input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CodexError {
+    /// The XML input is malformed (or cannot be read).
+    Xml {
+        line: u64,
+        column: u64,
+        message: String,
+    },
+
+    /// A field is invalid, in strict mode.
+    Invalid(Warning),
+
+    /// More invalid fields than allowed, the last one being given.
+    TooManyErrors {
+        max: usize,
+        last: Warning,
+    },
+}
+
+/// Invalid field, skipped while reading a Codex export,
+/// positioned at the 1-based line and column of the XML input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    /// Index of the book in the export (0-based)
+    pub book: usize,
+    pub line: u64,
+    pub column: u64,
+    pub message: String,
+}
+
 // ---
 
-impl ToString for Author {
-    fn to_string(&self) -> String {
-        format!("Author[ {}, {}, {}, ]",
-                self.first_name, self.last_name, self.name)
+impl Display for Author {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "Author[ {}, {}, {}, ]",
+               self.first_name, self.last_name, self.name)
     }
 }
 
 impl Display for Book {
     fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
-        let isbns: Vec<String> =
-            self.isbn.iter().map(|i| i.to_string()).collect();
+        let isbns: Vec<String> = self.isbn.iter().
+            map(|i| i.hyphenated().unwrap_or_else(|| i.to_string())).collect();
 
         let authors: Vec<String> =
             self.authors.iter().map(|a| a.to_string()).collect();
 
         write!(
             formatter,
-            "Book[ #title[{}], #authors[{}], #kind[{}], {}, {}, {}, {}, #summary[{}], #cover[{}] ]",
+            "Book[ #title[{}], #series[{}], #authors[{}], #kind[{}], {}, {}, {}, {}, #language[{}], #summary[{}], #cover[{}] ]",
             ellipsis(&self.title, 30),
+            self.volume.map_or_else(
+                || self.series.to_string(),
+                |v| format!("{} #{}", self.series, v)),
             authors.join(", "),
             self.kind.join(", "),
             self.pubdate.map(|d| d.to_string()).unwrap_or("".to_string()),
             self.publisher,
             self.pages,
             isbns.join(", "),
+            self.language,
             ellipsis(&self.summary, 30),
             self.cover)
     }
 }
 
-fn ellipsis(text: &String, max: usize) -> String {
-    let str = text.as_str();
-
-    if text.len() > max {
-        let mut prepared: String = str.chars().into_iter().take(max).collect();
+pub(crate) fn ellipsis(text: &str, max: usize) -> String {
+    if text.chars().count() > max {
+        let mut prepared: String = text.chars().take(max).collect();
 
         prepared.push_str("...");
 
         return prepared;
     }
 
-    return String::from(str);
+    String::from(text)
 }
 
 impl PartialEq for Author {
@@ -107,13 +215,71 @@ impl PartialEq for Author {
 impl PartialEq for Book {
     fn eq(&self, other: &Self) -> bool {
         self.title == other.title &&
+            self.subtitle == other.subtitle &&
             self.authors == other.authors &&
             self.kind == other.kind &&
             self.pubdate == other.pubdate &&
             self.publisher == other.publisher &&
             self.pages == other.pages &&
             self.isbn == other.isbn &&
+            self.identifiers == other.identifiers &&
             self.summary == other.summary &&
-            self.cover == other.cover
+            self.cover == other.cover &&
+            self.language == other.language &&
+            self.series == other.series &&
+            self.volume == other.volume &&
+            self.format == other.format &&
+            self.rating == other.rating &&
+            self.read == other.read &&
+            self.read_start == other.read_start &&
+            self.read_end == other.read_end &&
+            self.loaned_to == other.loaned_to &&
+            self.notes == other.notes &&
+            self.tags == other.tags
+    }
+}
+
+impl Display for CodexError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodexError::Xml { line, column, message } =>
+                write!(formatter, "XML error at {}:{}: {}",
+                       line, column, message),
+
+            CodexError::Invalid(w) =>
+                write!(formatter, "Invalid field at {}", w),
+
+            CodexError::TooManyErrors { max, last } =>
+                write!(formatter, "Too many invalid fields (more than {}), \
+                                   last at {}", max, last),
+        }
+    }
+}
+
+impl std::error::Error for CodexError {}
+
+impl Display for Warning {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl From<xml::reader::Error> for CodexError {
+    fn from(cause: xml::reader::Error) -> Self {
+        use xml::common::Position;
+
+        let pos = cause.position();
+
+        CodexError::Xml {
+            line: pos.row + 1,
+            column: pos.column + 1,
+            message: cause.msg().to_string(),
+        }
+    }
+}
+
+impl From<CodexError> for Error {
+    fn from(cause: CodexError) -> Self {
+        Error::new(ErrorKind::InvalidData, cause)
     }
 }
@@ -1,354 +1,1106 @@
 use log::{info, warn};
 
-use std::io::Read;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{Error, Read, Result as IoResult, Write};
+use std::rc::Rc;
 
+use xml::EmitterConfig;
+use xml::common::{Position, TextPosition};
 use xml::name::OwnedName;
 use xml::reader::{EventReader, XmlEvent};
+use xml::writer::{EventWriter, XmlEvent as WriterEvent};
 
-use super::{Book, empty_author, empty_book};
+use super::{Author, Book, CodexError, Warning, empty_author, empty_book};
 use crate::model::Isbn;
+use crate::pipeline::{BookSink, BookSource, Entry};
 
-pub fn parse<A: Read, F: FnMut(&Book) -> ()>(r: A, mut f: F) {
-    let parser = EventReader::new(r);
-
-    let name_name = OwnedName::local("name");
-    let type_name = OwnedName::local("type");
-    let value_name = OwnedName::local("value");
-
-    let first_name = OwnedName::local("firstName");
-    let last_name = OwnedName::local("lastName");
-
-    let book_name = OwnedName::local("book");
-    let book_title = OwnedName::local("title");
-    let book_publisher = OwnedName::local("publisher");
-    let book_identifiers = OwnedName::local("identifiers");
-    let book_identifier = OwnedName::local("identifier");
-    let book_page_count = OwnedName::local("pageCount");
-    let book_publish_date = OwnedName::local("publishDate");
-    let book_description = OwnedName::local("description");
-    let book_categories = OwnedName::local("categories");
-    let book_category = OwnedName::local("category");
-    let book_cover_url = OwnedName::local("coverUrl");
-    let book_authors = OwnedName::local("authors");
-    let book_author = OwnedName::local("author");
-
-    let isbn_10 = "ISBN_10".to_string();
-    let isbn_13 = "ISBN_13".to_string();
-    let google_id = "GOOGLE_ID".to_string();
-
-    let mut in_book = false;
-    let mut in_book_title = false;
-    let mut in_book_page_count = false;
-    let mut in_book_publish_date = false;
-    let mut in_book_description = false;
-    let mut in_book_publisher: u8 = 0;
-    let mut in_book_identifier: u8 = 0;
-    let mut in_book_category: u8 = 0;
-    let mut in_book_cover = false;
-    let mut in_book_author = 0;
+/// Returns a reader pulling the books from the given Codex XML input.
+pub fn parse<A: Read>(r: A) -> BookReader<A> {
+    BookReader::new(r)
+}
+
+/// Pull-based reader of the books from a Codex XML export.
+///
+/// Each successfully parsed `<book>` is yielded as `Ok`;
+/// a malformed input yields a single `Err` positioned in the XML,
+/// after which the iteration ends.
+///
+/// The invalid fields are skipped as warnings, unless the reader is `strict`
+/// or more than `max_errors` are found, in which case an `Err` ends it.
+pub struct BookReader<A: Read> {
+    parser: EventReader<A>,
+    state: State,
+    finished: bool,
+}
+
+impl<A: Read> BookReader<A> {
+    pub fn new(r: A) -> BookReader<A> {
+        BookReader {
+            parser: EventReader::new(r),
+            state: State::new(),
+            finished: false,
+        }
+    }
+
+    /// Fails on the first invalid field.
+    pub fn strict(self) -> BookReader<A> {
+        self.max_errors(0)
+    }
+
+    /// Fails once more than `max` invalid fields are found.
+    pub fn max_errors(mut self, max: usize) -> BookReader<A> {
+        self.state.max_errors = Some(max);
+        self
+    }
+
+    /// Returns the warnings about the invalid fields,
+    /// updated as the books are read.
+    pub fn warnings(&self) -> Warnings {
+        self.state.warnings.clone()
+    }
+}
+
+/// Warnings of a `BookReader`, shared with it.
+#[derive(Clone, Default)]
+pub struct Warnings(Rc<RefCell<Vec<Warning>>>);
+
+impl Warnings {
+    /// Returns the warnings reported since the previous call.
+    pub fn take(&self) -> Vec<Warning> {
+        std::mem::take(&mut *self.0.borrow_mut())
+    }
+}
+
+impl<A: Read> BookSource for BookReader<A> {
+    fn next_book(&mut self) -> Option<IoResult<Book>> {
+        self.next().map(|res| res.map_err(Error::from))
+    }
+
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        self.state.warnings.take()
+    }
+}
+
+impl<A: Read> Iterator for BookReader<A> {
+    type Item = Result<Book, CodexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        loop {
+            match self.parser.next() {
+                Ok(XmlEvent::EndDocument) => {
+                    self.finished = true;
+
+                    return None;
+                }
+
+                Ok(event) => {
+                    self.state.position = self.parser.position();
+
+                    let book = self.state.on_event(event);
+
+                    if let Some(cause) = self.state.failure.take() {
+                        self.finished = true;
+
+                        return Some(Err(cause));
+                    }
+
+                    if let Some(b) = book {
+                        return Some(Ok(b));
+                    }
+                }
+
+                Err(cause) => {
+                    self.finished = true;
+
+                    return Some(Err(CodexError::from(cause)));
+                }
+            }
+        }
+    }
+}
+
+// ---
+
+struct Names {
+    name: OwnedName,
+    type_name: OwnedName,
+    value: OwnedName,
+
+    first_name: OwnedName,
+    last_name: OwnedName,
+
+    book: OwnedName,
+    book_title: OwnedName,
+    book_publisher: OwnedName,
+    book_identifiers: OwnedName,
+    book_identifier: OwnedName,
+    book_page_count: OwnedName,
+    book_publish_date: OwnedName,
+    book_description: OwnedName,
+    book_categories: OwnedName,
+    book_category: OwnedName,
+    book_cover_url: OwnedName,
+    book_authors: OwnedName,
+    book_author: OwnedName,
+    book_subtitle: OwnedName,
+    book_language: OwnedName,
+    book_series: OwnedName,
+    book_volume: OwnedName,
+    book_format: OwnedName,
+    book_rating: OwnedName,
+    book_read: OwnedName,
+    book_read_start: OwnedName,
+    book_read_end: OwnedName,
+    book_loaned_to: OwnedName,
+    book_notes: OwnedName,
+    book_tags: OwnedName,
+    book_tag: OwnedName,
+
+    isbn_10: String,
+    isbn_13: String,
+    google_id: String,
+}
+
+impl Names {
+    fn new() -> Names {
+        Names {
+            name: OwnedName::local("name"),
+            type_name: OwnedName::local("type"),
+            value: OwnedName::local("value"),
+
+            first_name: OwnedName::local("firstName"),
+            last_name: OwnedName::local("lastName"),
+
+            book: OwnedName::local("book"),
+            book_title: OwnedName::local("title"),
+            book_publisher: OwnedName::local("publisher"),
+            book_identifiers: OwnedName::local("identifiers"),
+            book_identifier: OwnedName::local("identifier"),
+            book_page_count: OwnedName::local("pageCount"),
+            book_publish_date: OwnedName::local("publishDate"),
+            book_description: OwnedName::local("description"),
+            book_categories: OwnedName::local("categories"),
+            book_category: OwnedName::local("category"),
+            book_cover_url: OwnedName::local("coverUrl"),
+            book_authors: OwnedName::local("authors"),
+            book_author: OwnedName::local("author"),
+            book_subtitle: OwnedName::local("subtitle"),
+            book_language: OwnedName::local("language"),
+            book_series: OwnedName::local("series"),
+            book_volume: OwnedName::local("volume"),
+            book_format: OwnedName::local("format"),
+            book_rating: OwnedName::local("rating"),
+            book_read: OwnedName::local("read"),
+            book_read_start: OwnedName::local("readStartDate"),
+            book_read_end: OwnedName::local("readEndDate"),
+            book_loaned_to: OwnedName::local("loanedTo"),
+            book_notes: OwnedName::local("notes"),
+            book_tags: OwnedName::local("tags"),
+            book_tag: OwnedName::local("tag"),
+
+            isbn_10: "ISBN_10".to_string(),
+            isbn_13: "ISBN_13".to_string(),
+            google_id: "GOOGLE_ID".to_string(),
+        }
+    }
+}
+
+struct State {
+    names: Names,
+
+    in_book: bool,
+    in_book_title: bool,
+    in_book_page_count: bool,
+    in_book_publish_date: bool,
+    in_book_description: bool,
+    in_book_publisher: u8,
+    in_book_identifier: u8,
+    in_book_category: u8,
+    in_book_cover: bool,
+    in_book_author: u8,
+    in_book_subtitle: bool,
+    in_book_language: bool,
+    in_book_series: bool,
+    in_book_volume: bool,
+    in_book_format: bool,
+    in_book_rating: bool,
+    in_book_read: bool,
+    in_book_read_start: bool,
+    in_book_read_end: bool,
+    in_book_loaned_to: bool,
+    in_book_notes: bool,
+    in_book_tag: u8,
 
     // Accumulated book properties
-    let mut book = empty_book();
-    let mut id_type = 0;
-    let mut author = empty_author();
+    book: Book,
+    id_type: u8,
+    author: Author,
+
+    /// Number of books already read
+    books: usize,
+    position: TextPosition,
+    warnings: Warnings,
+
+    /// Number of invalid fields
+    errors: usize,
+    max_errors: Option<usize>,
+    /// Invalid field ending the reading
+    failure: Option<CodexError>,
+}
+
+impl State {
+    fn new() -> State {
+        State {
+            names: Names::new(),
+
+            in_book: false,
+            in_book_title: false,
+            in_book_page_count: false,
+            in_book_publish_date: false,
+            in_book_description: false,
+            in_book_publisher: 0,
+            in_book_identifier: 0,
+            in_book_category: 0,
+            in_book_cover: false,
+            in_book_author: 0,
+            in_book_subtitle: false,
+            in_book_language: false,
+            in_book_series: false,
+            in_book_volume: false,
+            in_book_format: false,
+            in_book_rating: false,
+            in_book_read: false,
+            in_book_read_start: false,
+            in_book_read_end: false,
+            in_book_loaned_to: false,
+            in_book_notes: false,
+            in_book_tag: 0,
+
+            book: empty_book(),
+            id_type: 0,
+            author: empty_author(),
+
+            books: 0,
+            position: TextPosition::new(),
+            warnings: Warnings::default(),
+
+            errors: 0,
+            max_errors: None,
+            failure: None,
+        }
+    }
 
-    for e in parser {
-        match e {
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                name == book_name) => {
-                in_book = true;
+    /// Reports an invalid field of the current book,
+    /// failing if no more are allowed.
+    fn warn(&mut self, message: String) {
+        let w = Warning {
+            book: self.books,
+            line: self.position.row + 1,
+            column: self.position.column + 1,
+            message,
+        };
+
+        warn!(target: "xml", "{}", w);
+
+        self.errors += 1;
+
+        match self.max_errors {
+            Some(0) if self.failure.is_none() =>
+                self.failure = Some(CodexError::Invalid(w.clone())),
+
+            Some(max) if self.errors > max && self.failure.is_none() =>
+                self.failure = Some(CodexError::TooManyErrors {
+                    max,
+                    last: w.clone(),
+                }),
+
+            _ => (),
+        }
+
+        self.warnings.0.borrow_mut().push(w);
+    }
+
+    /// Updates the parsing state according the given XML event,
+    /// returning the book if the event completes it.
+    fn on_event(&mut self, event: XmlEvent) -> Option<Book> {
+        let n = &self.names;
+
+        match event {
+            XmlEvent::StartElement { name, .. } if name == n.book => {
+                self.in_book = true;
+            }
+
+            XmlEvent::EndElement { name } if name == n.book => {
+                self.in_book = false;
+                self.id_type = 0;
+                self.books += 1;
+
+                return Some(std::mem::replace(&mut self.book, empty_book()));
+            }
+
+            // ---
+
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_title) => {
+                self.in_book_title = true;
+            }
+
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_title) => {
+                self.in_book_title = false;
+            }
+
+            // ---
+
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_cover_url) => {
+                self.in_book_cover = true;
+            }
+
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_cover_url) => {
+                self.in_book_cover = false;
+            }
+
+            // ---
+
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_publisher) => {
+                self.in_book_publisher = 1;
+            }
+
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_publisher) => {
+                self.in_book_publisher = 0;
             }
 
-            Ok(XmlEvent::EndElement { name }) if (name == book_name) => {
-                f(&book);
+            XmlEvent::StartElement { name, .. } if (
+                (self.in_book_publisher == 1) && name == n.name) => {
+                self.in_book_publisher = 2;
+            }
+
+            // ---
+
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_categories) => {
+                self.in_book_category = 1;
+            }
 
-                in_book = false;
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_categories) => {
+                self.in_book_category = 0;
+            }
 
-                book = empty_book();
-                id_type = 0;
+            XmlEvent::StartElement { name, .. } if (
+                (self.in_book_category == 1) && name == n.book_category) => {
+                self.in_book_category = 2;
             }
 
             // ---
 
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                in_book && name == book_title) => {
-                in_book_title = true;
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_identifiers) => {
+                self.in_book_identifier = 1;
+            }
+
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_identifiers) => {
+                self.in_book_identifier = 0;
+            }
+
+            XmlEvent::StartElement { name, .. } if (
+                (self.in_book_identifier == 1) && name == n.book_identifier) => {
+                self.in_book_identifier = 2;
             }
 
-            Ok(XmlEvent::EndElement { name }) if (
-                in_book && name == book_title) => {
-                in_book_title = false;
+            XmlEvent::EndElement { name } if (
+                (self.in_book_identifier == 2) && name == n.book_identifier) => {
+                self.in_book_identifier = 1;
+            }
+
+            XmlEvent::StartElement { name, .. } if (
+                (self.in_book_identifier == 2) && name == n.type_name) => {
+                self.in_book_identifier = 3;
+            }
+
+            XmlEvent::EndElement { name } if (
+                (self.in_book_identifier == 3) && name == n.type_name) => {
+                self.in_book_identifier = 2;
+            }
+
+            XmlEvent::StartElement { name, .. } if (
+                (self.in_book_identifier == 2) && name == n.value) => {
+                self.in_book_identifier = 4;
+            }
+
+            XmlEvent::EndElement { name } if (
+                (self.in_book_identifier == 4) && name == n.value) => {
+                self.in_book_identifier = 2;
             }
 
             // ---
 
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                in_book && name == book_cover_url) => {
-                in_book_cover = true;
+            XmlEvent::StartElement { name, .. } if name == n.book_authors => {
+                self.in_book_author = 1;
+            }
+
+            XmlEvent::EndElement { name } if name == n.book_authors => {
+                self.in_book_author = 0;
+            }
+
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book_author == 1 && name == n.book_author) => {
+                self.in_book_author = 2;
+            }
+
+            XmlEvent::EndElement { name } if (
+                self.in_book_author >= 2 && name == n.book_author) => {
+                self.in_book_author = 1;
+
+                self.book.authors.push(
+                    std::mem::replace(&mut self.author, empty_author()));
+            }
+
+            XmlEvent::EndElement { name } if (
+                self.in_book_author >= 3 && (
+                    name == n.first_name ||
+                        name == n.last_name ||
+                        name == n.name)) => {
+                self.in_book_author = 2;
+            }
+
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book_author >= 2 && name == n.first_name) => {
+                self.in_book_author = 3;
+            }
+
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book_author >= 2 && name == n.last_name) => {
+                self.in_book_author = 4;
             }
 
-            Ok(XmlEvent::EndElement { name }) if (
-                in_book && name == book_cover_url) => {
-                in_book_cover = false;
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book_author >= 2 && name == n.name) => {
+                self.in_book_author = 5;
             }
 
             // ---
 
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                in_book && name == book_publisher) => {
-                in_book_publisher = 1;
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_page_count) => {
+                self.in_book_page_count = true;
             }
 
-            Ok(XmlEvent::EndElement { name }) if (
-                in_book && name == book_publisher) => {
-                in_book_publisher = 0;
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_page_count) => {
+                self.in_book_page_count = false;
             }
 
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                (in_book_publisher == 1) && name == name_name) => {
-                in_book_publisher = 2;
+            // ---
+
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_publish_date) => {
+                self.in_book_publish_date = true;
+            }
+
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_publish_date) => {
+                self.in_book_publish_date = false;
             }
 
             // ---
 
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                in_book && name == book_categories) => {
-                in_book_category = 1;
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_description) => {
+                self.in_book_description = true;
             }
 
-            Ok(XmlEvent::EndElement { name }) if (
-                in_book && name == book_categories) => {
-                in_book_category = 0;
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_description) => {
+                self.in_book_description = false;
             }
 
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                (in_book_category == 1) && name == book_category) => {
-                in_book_category = 2;
+            // ---
+
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_subtitle) => {
+                self.in_book_subtitle = true;
+            }
+
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_subtitle) => {
+                self.in_book_subtitle = false;
             }
 
             // ---
 
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                in_book && name == book_identifiers) => {
-                in_book_identifier = 1;
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_language) => {
+                self.in_book_language = true;
             }
 
-            Ok(XmlEvent::EndElement { name }) if (
-                in_book && name == book_identifiers) => {
-                in_book_identifier = 0;
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_language) => {
+                self.in_book_language = false;
             }
 
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                (in_book_identifier == 1) && name == book_identifier) => {
-                in_book_identifier = 2;
+            // ---
+
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_series) => {
+                self.in_book_series = true;
             }
 
-            Ok(XmlEvent::EndElement { name }) if (
-                (in_book_identifier == 2) && name == book_identifier) => {
-                in_book_identifier = 1;
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_series) => {
+                self.in_book_series = false;
             }
 
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                (in_book_identifier == 2) && name == type_name) => {
-                in_book_identifier = 3;
+            // ---
+
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_volume) => {
+                self.in_book_volume = true;
             }
 
-            Ok(XmlEvent::EndElement { name }) if (
-                (in_book_identifier == 3) && name == type_name) => {
-                in_book_identifier = 2;
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_volume) => {
+                self.in_book_volume = false;
             }
 
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                (in_book_identifier == 2) && name == value_name) => {
-                in_book_identifier = 4;
+            // ---
+
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_format) => {
+                self.in_book_format = true;
             }
 
-            Ok(XmlEvent::EndElement { name }) if (
-                (in_book_identifier == 4) && name == value_name) => {
-                in_book_identifier = 2;
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_format) => {
+                self.in_book_format = false;
             }
 
             // ---
 
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                name == book_authors) => {
-                in_book_author = 1;
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_rating) => {
+                self.in_book_rating = true;
+            }
+
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_rating) => {
+                self.in_book_rating = false;
             }
 
-            Ok(XmlEvent::EndElement { name }) if (name == book_authors) => {
-                in_book_author = 0;
+            // ---
 
-                book.authors.push(author);
-                
-                author = empty_author();
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_read) => {
+                self.in_book_read = true;
             }
 
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                in_book_author == 1 && name == book_author) => {
-                in_book_author = 2;
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_read) => {
+                self.in_book_read = false;
             }
 
-            Ok(XmlEvent::EndElement { name }) if (name == book_author) => {
-                in_book_author = 1;
+            // ---
+
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_read_start) => {
+                self.in_book_read_start = true;
             }
 
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                in_book_author >= 2 && name == first_name) => {
-                in_book_author = 3;
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_read_start) => {
+                self.in_book_read_start = false;
             }
 
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                in_book_author >= 2 && name == last_name) => {
-                in_book_author = 4;
+            // ---
+
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_read_end) => {
+                self.in_book_read_end = true;
             }
 
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                in_book_author >= 2 && name == name_name) => {
-                in_book_author = 5;
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_read_end) => {
+                self.in_book_read_end = false;
             }
 
             // ---
 
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                in_book && name == book_page_count) => {
-                in_book_page_count = true;
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_loaned_to) => {
+                self.in_book_loaned_to = true;
             }
 
-            Ok(XmlEvent::EndElement { name }) if (
-                in_book && name == book_page_count) => {
-                in_book_page_count = false;
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_loaned_to) => {
+                self.in_book_loaned_to = false;
             }
 
             // ---
 
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                in_book && name == book_publish_date) => {
-                in_book_publish_date = true;
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_notes) => {
+                self.in_book_notes = true;
             }
 
-            Ok(XmlEvent::EndElement { name }) if (
-                in_book && name == book_publish_date) => {
-                in_book_publish_date = false;
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_notes) => {
+                self.in_book_notes = false;
             }
 
             // ---
 
-            Ok(XmlEvent::StartElement { name, .. }) if (
-                in_book && name == book_description) => {
-                in_book_description = true;
+            XmlEvent::StartElement { name, .. } if (
+                self.in_book && name == n.book_tags) => {
+                self.in_book_tag = 1;
+            }
+
+            XmlEvent::EndElement { name } if (
+                self.in_book && name == n.book_tags) => {
+                self.in_book_tag = 0;
+            }
+
+            XmlEvent::StartElement { name, .. } if (
+                (self.in_book_tag == 1) && name == n.book_tag) => {
+                self.in_book_tag = 2;
+            }
+
+            XmlEvent::EndElement { name } if (
+                (self.in_book_tag == 2) && name == n.book_tag) => {
+                self.in_book_tag = 1;
+            }
+
+            XmlEvent::StartElement { name, .. } if (
+                (self.in_book_tag == 2) && name == n.name) => {
+                self.in_book_tag = 3;
             }
 
-            Ok(XmlEvent::EndElement { name }) if (
-                in_book && name == book_description) => {
-                in_book_description = false;
+            XmlEvent::EndElement { name } if (
+                (self.in_book_tag == 3) && name == n.name) => {
+                self.in_book_tag = 2;
             }
 
             // ---
 
-            Ok(XmlEvent::Characters(value)) if (in_book_title) => {
-                book.title = value;
+            XmlEvent::Characters(value) if self.in_book_title => {
+                self.book.title = value;
             }
 
-            Ok(XmlEvent::Characters(value)) if (in_book_cover) => {
-                book.cover = value;
+            XmlEvent::Characters(value) if self.in_book_cover => {
+                self.book.cover = value;
             }
 
-            Ok(XmlEvent::Characters(value)) if (in_book_publisher == 2) => {
-                book.publisher = value;
+            XmlEvent::Characters(value) if (self.in_book_publisher == 2) => {
+                self.book.publisher = value;
             }
 
-            Ok(XmlEvent::Characters(value)) if (in_book_category == 2) => {
-                book.kind.push(value);
+            XmlEvent::Characters(value) if (self.in_book_category == 2) => {
+                self.book.kind.push(value);
             }
 
-            Ok(XmlEvent::Characters(value)) if (in_book_author == 3) => {
-                author.first_name = value;
+            XmlEvent::Characters(value) if (self.in_book_author == 3) => {
+                self.author.first_name = value;
             }
 
-            Ok(XmlEvent::Characters(value)) if (in_book_author == 4) => {
-                author.last_name = value;
+            XmlEvent::Characters(value) if (self.in_book_author == 4) => {
+                self.author.last_name = value;
             }
 
-            Ok(XmlEvent::Characters(value)) if (in_book_author == 5) => {
-                author.name = value;
+            XmlEvent::Characters(value) if (self.in_book_author == 5) => {
+                self.author.name = value;
             }
 
-            Ok(XmlEvent::Characters(value)) if (
-                in_book_identifier == 3) => {
-                if value == isbn_10 {
-                    id_type = 10;
-                } else if value == isbn_13 {
-                    id_type = 13;
-                } else if value == google_id {
+            XmlEvent::Characters(value) if (self.in_book_identifier == 3) => {
+                if value == n.isbn_10 {
+                    self.id_type = 10;
+                } else if value == n.isbn_13 {
+                    self.id_type = 13;
+                } else if value == n.google_id {
                     info!(target: "xml", "Ignore Google identifier");
-                    id_type = 0;
+                    self.id_type = 0;
                 } else {
-                    warn!("Invalid ISBN type: {}", value);
-                    id_type = 0;
+                    self.warn(format!("Invalid ISBN type: {}", value));
+                    self.id_type = 0;
                 }
             }
 
-            Ok(XmlEvent::Characters(value)) if (
-                in_book_identifier == 4 && id_type > 0) => {
-                match id_type {
-                    10 => book.isbn.push(Isbn::Isbn10(value)),
+            XmlEvent::Characters(value) if (
+                self.in_book_identifier == 4 && self.id_type > 0) => {
+                match Isbn::parse(&value) {
+                    Err(cause) => {
+                        self.warn(format!(
+                            "Invalid ISBN{} '{}': {}",
+                            self.id_type, value, cause));
+                    }
 
-                    13 => {
-                        match value.parse::<u64>() {
-                            Err(cause) => {
-                                warn!("Invalid ISBN13 '{}': {}", value, cause);
-                            }
+                    Ok(isbn) => {
+                        self.book.isbn.push(isbn)
+                    }
+                }
+            }
+
+            XmlEvent::Characters(value) if self.in_book_publish_date => {
+                match time::parse(&value, "%F") {
+                    Ok(date) => self.book.pubdate = Some(date),
+
+                    Err(cause) => {
+                        self.warn(format!(
+                            "Invalid publication date '{}': {}", value, cause));
+                    }
+                }
+            }
+
+            XmlEvent::Characters(value) if self.in_book_description => {
+                self.book.summary = value;
+            }
+
+            XmlEvent::Characters(value) if self.in_book_page_count => {
+                match value.parse() {
+                    Err(cause) => {
+                        self.warn(format!(
+                            "Invalid pageCount '{}': {}", value, cause));
+                    }
 
-                            Ok(num) => {
-                                book.isbn.push(Isbn::Isbn13(num))
-                            }
-                        }
+                    Ok(p) => {
+                        self.book.pages = p;
                     }
-                    
-                    _ => warn!("Unexpected ISBN: {}", value),
                 }
+            }
 
+            XmlEvent::Characters(value) if self.in_book_subtitle => {
+                self.book.subtitle = value;
             }
 
-            Ok(XmlEvent::Characters(value)) if (in_book_publish_date) => {
-                match time::parse(value.to_string(), "%F") {
-                    Ok(date) => book.pubdate = Some(date),
+            XmlEvent::Characters(value) if self.in_book_language => {
+                self.book.language = value;
+            }
 
+            XmlEvent::Characters(value) if self.in_book_series => {
+                self.book.series = value;
+            }
+
+            XmlEvent::Characters(value) if self.in_book_volume => {
+                match value.parse() {
                     Err(cause) => {
-                        warn!("Invalid publication date '{}': {}",
-                              value, cause);
+                        self.warn(format!(
+                            "Invalid volume '{}': {}", value, cause));
+                    }
+
+                    Ok(v) => {
+                        self.book.volume = Some(v);
                     }
                 }
             }
 
-            Ok(XmlEvent::Characters(value)) if (in_book_description) => {
-                book.summary = value;
+            XmlEvent::Characters(value) if self.in_book_format => {
+                self.book.format = value;
             }
 
-            Ok(XmlEvent::Characters(value)) if (in_book_page_count) => {
+            XmlEvent::Characters(value) if self.in_book_rating => {
                 match value.parse() {
                     Err(cause) => {
-                        warn!("Invalid pageCount '{}': {}",
-                              value, cause);
+                        self.warn(format!(
+                            "Invalid rating '{}': {}", value, cause));
                     }
 
-                    Ok(p) => {
-                        book.pages = p;
+                    Ok(r) => {
+                        self.book.rating = Some(r);
+                    }
+                }
+            }
+
+            XmlEvent::Characters(value) if self.in_book_read => {
+                match value.parse() {
+                    Err(cause) => {
+                        self.warn(format!(
+                            "Invalid read status '{}': {}", value, cause));
+                    }
+
+                    Ok(r) => {
+                        self.book.read = r;
+                    }
+                }
+            }
+
+            XmlEvent::Characters(value) if self.in_book_read_start => {
+                match time::parse(&value, "%F") {
+                    Ok(date) => self.book.read_start = Some(date),
+
+                    Err(cause) => {
+                        self.warn(format!(
+                            "Invalid reading start date '{}': {}",
+                            value, cause));
+                    }
+                }
+            }
+
+            XmlEvent::Characters(value) if self.in_book_read_end => {
+                match time::parse(&value, "%F") {
+                    Ok(date) => self.book.read_end = Some(date),
+
+                    Err(cause) => {
+                        self.warn(format!(
+                            "Invalid reading end date '{}': {}", value, cause));
                     }
                 }
             }
 
+            XmlEvent::Characters(value) if self.in_book_loaned_to => {
+                self.book.loaned_to = value;
+            }
+
+            XmlEvent::Characters(value) if self.in_book_notes => {
+                self.book.notes = value;
+            }
+
+            XmlEvent::Characters(value) if (self.in_book_tag == 3) => {
+                self.book.tags.push(value);
+            }
+
             // ---
 
-            Ok(..) => {
-                //println!("Ok");
+            _ => (),
+        }
+
+        None
+    }
+}
+
+// ---
+
+/// Completion of the fields left empty in the books, from their ISBN
+/// (e.g. as `Pipeline::map` transform):
+/// the language from the ISBN registration group,
+/// and the publisher from the previous books with the same registrant.
+#[derive(Default)]
+pub struct IsbnInference {
+    /// Publishers by ISBN registrant, from the books already completed
+    publishers: HashMap<String, String>,
+}
+
+impl IsbnInference {
+    pub fn new() -> IsbnInference {
+        IsbnInference::default()
+    }
+
+    /// Returns the book with the inferred fields.
+    pub fn complete(&mut self, mut book: Book) -> Book {
+        if book.language.is_empty() {
+            if let Some(group) = book.isbn.iter().
+                find_map(|i| i.registration_group()) {
+                if let Some(lang) = group.agency.strip_suffix(" language") {
+                    info!(target: "mylib", "Infer language from ISBN group {}: {}",
+                          group.prefix, lang);
+
+                    book.language = lang.to_string();
+                }
             }
+        }
 
-            Err(cause) => warn!("Error = {}", cause),
+        if let Some(registrant) = book.isbn.iter().
+            find_map(|i| i.registrant()) {
+            if book.publisher.is_empty() {
+                if let Some(publisher) = self.publishers.get(&registrant) {
+                    info!(target: "mylib", "Infer publisher from ISBN registrant {}: {}",
+                          registrant, publisher);
+
+                    book.publisher = publisher.to_string();
+                }
+            } else {
+                self.publishers.insert(registrant, book.publisher.to_string());
+            }
         }
+
+        book
     }
 }
 
+// ---
+
+/// Writer of books as a Codex XML export (`<books version="2">`).
+///
+/// The `<books>` element is opened on creation,
+/// and must be closed using `finish`.
+pub struct BookWriter<W: Write> {
+    writer: EventWriter<W>,
+}
+
+impl<W: Write> BookWriter<W> {
+    pub fn new(w: W) -> IoResult<BookWriter<W>> {
+        let mut writer = EmitterConfig::new().
+            perform_indent(true).
+            create_writer(w);
+
+        writer.write(WriterEvent::start_element("books").
+                     attr("version", "2")).map_err(Error::other)?;
+
+        Ok(BookWriter { writer })
+    }
+
+    pub fn write(&mut self, book: &Book) -> IoResult<()> {
+        let w = &mut self.writer;
+
+        start(w, "book")?;
+
+        text_element(w, "title", &book.title)?;
+        text_element(w, "subtitle", &book.subtitle)?;
+
+        if !book.authors.is_empty() {
+            start(w, "authors")?;
+
+            for author in book.authors.iter() {
+                start(w, "author")?;
+                text_element(w, "firstName", &author.first_name)?;
+                text_element(w, "lastName", &author.last_name)?;
+                text_element(w, "name", &author.name)?;
+                end(w)?;
+            }
+
+            end(w)?;
+        }
+
+        if !book.publisher.is_empty() {
+            start(w, "publisher")?;
+            text_element(w, "name", &book.publisher)?;
+            end(w)?;
+        }
+
+        if !book.isbn.is_empty() {
+            start(w, "identifiers")?;
+
+            for isbn in book.isbn.iter() {
+                let (tpe, value) = match isbn {
+                    Isbn::Isbn13(i13) => ("ISBN_13", i13.to_string()),
+                    Isbn::Isbn10(i10) => ("ISBN_10", i10.to_string()),
+                };
+
+                start(w, "identifier")?;
+                text_element(w, "type", tpe)?;
+                text_element(w, "value", &value)?;
+                end(w)?;
+            }
+
+            end(w)?;
+        }
+
+        date_element(w, "publishDate", book.pubdate)?;
+        text_element(w, "description", &book.summary)?;
+        text_element(w, "language", &book.language)?;
+
+        if book.pages > 0 {
+            text_element(w, "pageCount", &book.pages.to_string())?;
+        }
+
+        text_element(w, "coverUrl", &book.cover)?;
+
+        if !book.kind.is_empty() {
+            start(w, "categories")?;
+
+            for kind in book.kind.iter() {
+                start(w, "category")?;
+                text_element(w, "name", kind)?;
+                end(w)?;
+            }
+
+            end(w)?;
+        }
+
+        text_element(w, "series", &book.series)?;
+
+        if let Some(v) = book.volume {
+            text_element(w, "volume", &v.to_string())?;
+        }
+
+        text_element(w, "format", &book.format)?;
+
+        if let Some(r) = book.rating {
+            text_element(w, "rating", &r.to_string())?;
+        }
+
+        text_element(w, "read", &book.read.to_string())?;
+        date_element(w, "readStartDate", book.read_start)?;
+        date_element(w, "readEndDate", book.read_end)?;
+        text_element(w, "loanedTo", &book.loaned_to)?;
+        text_element(w, "notes", &book.notes)?;
+
+        if !book.tags.is_empty() {
+            start(w, "tags")?;
+
+            for tag in book.tags.iter() {
+                start(w, "tag")?;
+                text_element(w, "name", tag)?;
+                end(w)?;
+            }
+
+            end(w)?;
+        }
+
+        end(w)
+    }
+
+    /// Closes the `<books>` element, and returns the underlying writer.
+    pub fn finish(mut self) -> IoResult<W> {
+        BookSink::finish(&mut self).map(|_| self.writer.into_inner())
+    }
+}
+
+impl<W: Write> BookSink for BookWriter<W> {
+    fn write(&mut self, entry: &Entry) -> IoResult<Option<Error>> {
+        BookWriter::write(self, &entry.book).map(|_| None)
+    }
+
+    /// Closes the `<books>` element, and flushes the underlying writer.
+    fn finish(&mut self) -> IoResult<()> {
+        end(&mut self.writer)?;
+
+        let inner = self.writer.inner_mut();
+
+        inner.write_all(b"\n").and_then(|_| inner.flush())
+    }
+}
+
+fn start<W: Write>(w: &mut EventWriter<W>, name: &str) -> IoResult<()> {
+    w.write(WriterEvent::start_element(name)).map_err(Error::other)
+}
+
+fn end<W: Write>(w: &mut EventWriter<W>) -> IoResult<()> {
+    w.write(WriterEvent::end_element()).map_err(Error::other)
+}
+
+/// Writes a simple text element, unless the value is empty.
+fn text_element<W: Write>(
+    w: &mut EventWriter<W>,
+    name: &str,
+    value: &str,
+) -> IoResult<()> {
+    if value.is_empty() {
+        return Ok(());
+    }
+
+    start(w, name)?;
+
+    w.write(WriterEvent::characters(value)).map_err(Error::other)?;
+
+    end(w)
+}
+
+fn date_element<W: Write>(
+    w: &mut EventWriter<W>,
+    name: &str,
+    value: Option<time::Date>,
+) -> IoResult<()> {
+    value.map_or(Ok(()), |d| text_element(w, name, &d.format("%F")))
+}
+
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
@@ -402,29 +1154,395 @@ mod tests {
   </book>
 </books>";
 
-        parse(
-            input.as_bytes(),
-            |book| assert_eq!(*book, Book {
-                title: "Accros du roc".to_string(),
-                authors: vec![ crate::codex::Author {
-                    first_name: "Terry".to_string(),
-                    last_name: "Pratchett".to_string(),
+        let books: Vec<Result<Book, CodexError>> =
+            parse(input.as_bytes()).collect();
+
+        assert_eq!(books, vec![ Ok(Book {
+            title: "Accros du roc".to_string(),
+            subtitle: "".to_string(),
+            authors: vec![ crate::codex::Author {
+                first_name: "Terry".to_string(),
+                last_name: "Pratchett".to_string(),
+                name: "Terry Pratchett".to_string(),
+            } ],
+            kind: vec![
+                "General".to_string(),
+                "Science Fiction".to_string(),
+                "Fiction".to_string(),
+            ],
+            pubdate: Some(time::date!(2012-07-10)),
+            publisher: "Pocket".to_string(),
+            pages: 411,
+            isbn: vec![
+                Isbn::Isbn13(9782266211963),
+                Isbn::Isbn10("226621196X".to_string()),
+            ],
+            identifiers: vec![],
+            summary: "Suzanne est une jeune étudiante discrète ...".to_string(),
+            cover: "http://bks0.books.google.fr/books?id=fwIHPwAACAAJ&printsec=frontcover&img=1&zoom=1&imgtk=AFLRE711A4q0LqeTgMfMz76VFvw0yiHbNPQOTK-8nFhitUSbS8At14EQS6gzXwN1w2phGjskOqburPHmt_5LiFZQHufvU2KZ9GCB_JyQ6LeZdKysJY6gPuQ&source=gbs_api".to_string(),
+            language: "français".to_string(),
+            series: "".to_string(),
+            volume: None,
+            format: "".to_string(),
+            rating: None,
+            read: false,
+            read_start: None,
+            read_end: None,
+            loaned_to: "".to_string(),
+            notes: "".to_string(),
+            tags: vec![],
+        }) ]);
+    }
+
+    #[test]
+    fn test_parse_personal_fields() {
+        let input = "<books version=\"2\">
+  <book>
+    <title>Va-t-en-guerre</title>
+    <subtitle>Les Annales du Disque-monde</subtitle>
+    <authors>
+      <author><name>Terry Pratchett</name></author>
+    </authors>
+    <language>français</language>
+    <series>Les Annales du Disque-monde</series>
+    <volume>21</volume>
+    <format>Paperback</format>
+    <rating>4.5</rating>
+    <read>true</read>
+    <readStartDate>2020-08-01</readStartDate>
+    <readEndDate>2020-08-15</readEndDate>
+    <loanedTo>Rincevent</loanedTo>
+    <notes>Dédicacé</notes>
+    <tags>
+      <tag><name>humour</name></tag>
+      <tag><name>fantasy</name></tag>
+    </tags>
+  </book>
+  <book>
+    <title>Sans série</title>
+    <volume>n/a</volume>
+    <read>false</read>
+  </book>
+</books>";
+
+        let books: Vec<Book> = parse(input.as_bytes()).
+            map(|b| b.unwrap()).collect();
+
+        let b = &books[0];
+
+        assert_eq!(b.title, "Va-t-en-guerre");
+        assert_eq!(b.subtitle, "Les Annales du Disque-monde");
+        assert_eq!(b.authors.len(), 1);
+        assert_eq!(b.language, "français");
+        assert_eq!(b.series, "Les Annales du Disque-monde");
+        assert_eq!(b.volume, Some(21));
+        assert_eq!(b.format, "Paperback");
+        assert_eq!(b.rating, Some(4.5));
+        assert!(b.read);
+        assert_eq!(b.read_start, Some(time::date!(2020-08-01)));
+        assert_eq!(b.read_end, Some(time::date!(2020-08-15)));
+        assert_eq!(b.loaned_to, "Rincevent");
+        assert_eq!(b.notes, "Dédicacé");
+        assert_eq!(b.tags, vec![ "humour".to_string(), "fantasy".to_string() ]);
+
+        let b = &books[1];
+
+        assert_eq!(b.title, "Sans série");
+        assert_eq!(b.series, "");
+        assert_eq!(b.volume, None);
+        assert!(!b.read);
+    }
+
+    #[test]
+    fn test_parse_lazily() {
+        let input = "<books version=\"2\">
+  <book><title>First</title></book>
+  <book><title>Second</title></book>
+  <book><title>Third</title></book>
+</books>";
+
+        let titles: Vec<String> = parse(input.as_bytes()).
+            take(2).map(|b| b.unwrap().title).collect();
+
+        assert_eq!(titles, vec![ "First".to_string(), "Second".to_string() ]);
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        let input = "<books version=\"2\">
+  <book><title>First</title></book>
+  <book><title>Second</titel></book>
+  <book><title>Third</title></book>
+</books>";
+
+        let mut reader = parse(input.as_bytes());
+
+        assert_eq!(reader.next().map(|b| b.map(|book| book.title)),
+                   Some(Ok("First".to_string())));
+
+        match reader.next() {
+            Some(Err(CodexError::Xml { line, column, .. })) => {
+                assert_eq!(line, 3);
+                assert_eq!(column, 29);
+            }
+
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_parse_co_authors() {
+        let input = "<books version=\"2\">
+  <book>
+    <title>Japon, Miscellanées</title>
+    <authors>
+      <author>
+        <firstName>Chantal</firstName>
+        <lastName>DELTENRE</lastName>
+        <name>Chantal DELTENRE</name>
+      </author>
+      <author>
+        <firstName>Maximilien</firstName>
+        <lastName>DAUBER</lastName>
+        <name>Maximilien DAUBER</name>
+      </author>
+    </authors>
+  </book>
+  <book>
+    <title>De bons présages</title>
+    <authors>
+      <author>
+        <name>Terry Pratchett</name>
+      </author>
+      <author>
+        <firstName>Neil</firstName>
+        <name>Neil Gaiman</name>
+      </author>
+    </authors>
+  </book>
+</books>";
+
+        let authors: Vec<Vec<crate::codex::Author>> = parse(input.as_bytes()).
+            map(|b| b.unwrap().authors).collect();
+
+        assert_eq!(authors, vec![
+            vec![
+                crate::codex::Author {
+                    first_name: "Chantal".to_string(),
+                    last_name: "DELTENRE".to_string(),
+                    name: "Chantal DELTENRE".to_string(),
+                },
+                crate::codex::Author {
+                    first_name: "Maximilien".to_string(),
+                    last_name: "DAUBER".to_string(),
+                    name: "Maximilien DAUBER".to_string(),
+                },
+            ],
+            vec![
+                crate::codex::Author {
+                    first_name: "".to_string(),
+                    last_name: "".to_string(),
                     name: "Terry Pratchett".to_string(),
-                } ],
-                kind: vec![
-                    "General".to_string(),
-                    "Science Fiction".to_string(),
-                    "Fiction".to_string(),
-                ],
-                pubdate: Some(time::date!(2012-07-10)),
-                publisher: "Pocket".to_string(),
-                pages: 411,
-                isbn: vec![
-                    Isbn::Isbn13(9782266211963),
-                    Isbn::Isbn10("226621196X".to_string()),
-                ],
-                summary: "Suzanne est une jeune étudiante discrète ...".to_string(),
-                cover: "http://bks0.books.google.fr/books?id=fwIHPwAACAAJ&printsec=frontcover&img=1&zoom=1&imgtk=AFLRE711A4q0LqeTgMfMz76VFvw0yiHbNPQOTK-8nFhitUSbS8At14EQS6gzXwN1w2phGjskOqburPHmt_5LiFZQHufvU2KZ9GCB_JyQ6LeZdKysJY6gPuQ&source=gbs_api".to_string(),
-            }));
+                },
+                crate::codex::Author {
+                    first_name: "Neil".to_string(),
+                    last_name: "".to_string(),
+                    name: "Neil Gaiman".to_string(),
+                },
+            ],
+        ]);
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let input = "<books version=\"2\">
+  <book>
+    <title>Va-t-en-guerre</title>
+    <authors>
+      <author>
+        <firstName>Terry</firstName>
+        <lastName>Pratchett</lastName>
+        <name>Terry Pratchett</name>
+      </author>
+    </authors>
+    <publisher><name>Pocket</name></publisher>
+    <identifiers>
+      <identifier><type>ISBN_13</type><value>9782266211963</value></identifier>
+      <identifier><type>ISBN_10</type><value>226621196X</value></identifier>
+    </identifiers>
+    <publishDate>2012-07-10</publishDate>
+    <description>Ankh &amp; Morpork &lt;3</description>
+    <language>français</language>
+    <pageCount>411</pageCount>
+    <categories>
+      <category><name>Fiction</name></category>
+    </categories>
+    <series>Les Annales du Disque-monde</series>
+    <volume>21</volume>
+    <rating>4.5</rating>
+    <read>true</read>
+    <readEndDate>2020-08-15</readEndDate>
+    <notes>Dédicacé</notes>
+    <tags><tag><name>humour</name></tag></tags>
+  </book>
+  <book>
+    <title>Second</title>
+  </book>
+</books>";
+
+        let books: Vec<Book> = parse(input.as_bytes()).
+            map(|b| b.unwrap()).collect();
+
+        let mut w = BookWriter::new(vec![]).unwrap();
+
+        for book in books.iter() {
+            w.write(book).unwrap();
+        }
+
+        let out = w.finish().unwrap();
+
+        let written: Vec<Book> = parse(out.as_slice()).
+            map(|b| b.unwrap()).collect();
+
+        assert_eq!(written, books);
+    }
+
+    #[test]
+    fn test_parse_invalid_isbn() {
+        let input = "<books version=\"2\">
+  <book>
+    <title>Accros du roc</title>
+    <identifiers>
+      <identifier><type>ISBN_13</type><value>9782266211964</value></identifier>
+      <identifier><type>ISBN_10</type><value>2-266-21196-x</value></identifier>
+    </identifiers>
+  </book>
+</books>";
+
+        let isbn: Vec<Isbn> = parse(input.as_bytes()).
+            flat_map(|b| b.unwrap().isbn).collect();
+
+        assert_eq!(isbn, vec![ Isbn::Isbn10("226621196X".to_string()) ]);
+    }
+
+    #[test]
+    fn test_isbn_inference() {
+        let input = "<books version=\"2\">
+  <book>
+    <title>Accros du roc</title>
+    <publisher><name>Pocket</name></publisher>
+    <identifiers>
+      <identifier><type>ISBN_13</type><value>9782266211963</value></identifier>
+    </identifiers>
+    <language>français</language>
+  </book>
+  <book>
+    <title>Les Tribulations d'un mage en Aurient</title>
+    <identifiers>
+      <identifier><type>ISBN_10</type><value>2266111566</value></identifier>
+    </identifiers>
+  </book>
+  <book>
+    <title>Sans ISBN</title>
+  </book>
+</books>";
+
+        let parsed: Vec<Book> = parse(input.as_bytes()).
+            map(|b| b.unwrap()).collect();
+
+        // Not inferred by the parser
+        assert_eq!(parsed[1].language, "");
+        assert_eq!(parsed[1].publisher, "");
+
+        let mut inference = IsbnInference::new();
+        let books: Vec<(String, String)> = parsed.into_iter().
+            map(|b| inference.complete(b)).
+            map(|b| (b.language, b.publisher)).collect();
+
+        assert_eq!(books, vec![
+            ("français".to_string(), "Pocket".to_string()),
+            ("French".to_string(), "Pocket".to_string()),
+            ("".to_string(), "".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_warnings() {
+        let input = "<books version=\"2\">
+  <book>
+    <title>Accros du roc</title>
+    <pageCount>many</pageCount>
+  </book>
+  <book>
+    <title>Ally</title>
+    <publishDate>2013-13-01</publishDate>
+  </book>
+</books>";
+
+        let reader = parse(input.as_bytes());
+        let warnings = reader.warnings();
+
+        let titles: Vec<String> = reader.map(|b| b.unwrap().title).collect();
+
+        assert_eq!(titles.len(), 2);
+
+        let w = warnings.take();
+
+        assert_eq!(w.len(), 2);
+
+        assert_eq!(w[0].book, 0);
+        assert_eq!((w[0].line, w[0].column), (4, 16));
+        assert!(w[0].message.starts_with("Invalid pageCount 'many'"));
+
+        assert_eq!(w[1].book, 1);
+        assert_eq!(w[1].line, 8);
+
+        assert!(warnings.take().is_empty());
+    }
+
+    #[test]
+    fn test_parse_strict() {
+        let input = "<books version=\"2\">
+  <book>
+    <title>Accros du roc</title>
+    <identifiers>
+      <identifier><type>ASIN</type><value>B00K</value></identifier>
+    </identifiers>
+  </book>
+  <book>
+    <title>Ally</title>
+    <pageCount>many</pageCount>
+  </book>
+</books>";
+
+        let lenient: Vec<_> = parse(input.as_bytes()).collect();
+
+        assert_eq!(lenient.len(), 2);
+        assert!(lenient.iter().all(|b| b.is_ok()));
+
+        let strict: Vec<_> = parse(input.as_bytes()).strict().collect();
+
+        assert_eq!(strict.len(), 1);
+        assert_eq!(strict[0].as_ref().err().map(|e| e.to_string()),
+                   Some("Invalid field at 5:25: Invalid ISBN type: ASIN".
+                        to_string()));
+
+        let bounded: Vec<_> =
+            parse(input.as_bytes()).max_errors(1).collect();
+
+        assert_eq!(bounded.len(), 2);
+        assert!(bounded[0].is_ok());
+
+        match &bounded[1] {
+            Err(CodexError::TooManyErrors { max: 1, last }) =>
+                assert_eq!((last.line, last.column), (10, 16)),
+
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        assert_eq!(parse(input.as_bytes()).max_errors(2).
+                   filter(|b| b.is_ok()).count(), 2);
     }
 }
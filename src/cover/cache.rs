@@ -0,0 +1,244 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use sha2::{Digest, Sha256};
+
+/// On-disk cache of the cover images.
+///
+/// The image data are content-addressed (`objects/<sha256>`),
+/// and indexed by URL and by ISBN (`index/<sha256 of key>.json`).
+pub struct CoverCache {
+    dir: PathBuf,
+}
+
+/// Cached cover metadata
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// Digest of the image data
+    pub object: String,
+    pub content_type: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl CoverCache {
+    /// Opens the cache in the given directory (created if needed).
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<CoverCache> {
+        let dir = dir.as_ref().to_path_buf();
+
+        fs::create_dir_all(dir.join("objects")).
+            and_then(|_| fs::create_dir_all(dir.join("index"))).
+            map(|_| CoverCache { dir })
+    }
+
+    /// Returns the default cache directory
+    /// (`$XDG_CACHE_HOME/codex-mylib/covers` or `~/.cache/codex-mylib/covers`).
+    pub fn default_dir() -> Option<PathBuf> {
+        std::env::var_os("XDG_CACHE_HOME").map(PathBuf::from).
+            or_else(|| std::env::var_os("HOME").
+                    map(|home| Path::new(&home).join(".cache"))).
+            map(|cache| cache.join("codex-mylib").join("covers"))
+    }
+
+    /// Returns the entry cached for the URL (or else for the ISBN, if any).
+    pub fn lookup(&self, url: &str, isbn: &str) -> Result<Option<Entry>> {
+        match self.get(&url_key(url))? {
+            None if !isbn.is_empty() => self.get(&isbn_key(isbn)),
+            entry => Ok(entry),
+        }
+    }
+
+    /// Returns the entry cached for the URL only.
+    pub fn lookup_url(&self, url: &str) -> Result<Option<Entry>> {
+        self.get(&url_key(url))
+    }
+
+    /// Reads the image data for a cached entry.
+    pub fn read(&self, entry: &Entry) -> Result<Vec<u8>> {
+        fs::read(self.object_path(&entry.object))
+    }
+
+    /// Stores the image data, indexed by URL and ISBN (if not empty).
+    pub fn store(
+        &self,
+        url: &str,
+        isbn: &str,
+        content_type: &str,
+        etag: Option<String>,
+        last_modified: Option<String>,
+        data: &[u8],
+    ) -> Result<Entry> {
+        let entry = Entry {
+            object: hex_digest(data),
+            content_type: content_type.to_string(),
+            etag,
+            last_modified,
+        };
+
+        let object_path = self.object_path(&entry.object);
+
+        if !object_path.exists() {
+            write_atomically(&object_path, data)?;
+        }
+
+        self.put(&url_key(url), &entry)?;
+
+        if !isbn.is_empty() {
+            self.put(&isbn_key(isbn), &entry)?;
+        }
+
+        Ok(entry)
+    }
+
+    // ---
+
+    fn object_path(&self, object: &str) -> PathBuf {
+        self.dir.join("objects").join(object)
+    }
+
+    fn index_path(&self, key: &str) -> PathBuf {
+        self.dir.join("index").
+            join(format!("{}.json", hex_digest(key.as_bytes())))
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Entry>> {
+        let path = self.index_path(key);
+
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let meta = fs::read_to_string(&path).and_then(
+            |s| json::parse(&s).map_err(|cause| Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid cover cache entry '{}': {}",
+                        path.display(), cause))))?;
+
+        let opt = |name: &str| meta[name].as_str().map(|s| s.to_string());
+
+        match (opt("object"), opt("contentType")) {
+            (Some(object), Some(content_type)) => {
+                if self.object_path(&object).exists() {
+                    Ok(Some(Entry {
+                        object,
+                        content_type,
+                        etag: opt("etag"),
+                        last_modified: opt("lastModified"),
+                    }))
+                } else {
+                    Ok(None)
+                }
+            }
+
+            _ => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Incomplete cover cache entry: {}", path.display()))),
+        }
+    }
+
+    fn put(&self, key: &str, entry: &Entry) -> Result<()> {
+        let mut meta = json::object!{
+            key: key,
+            object: entry.object.as_str(),
+            contentType: entry.content_type.as_str(),
+        };
+
+        if let Some(etag) = &entry.etag {
+            meta["etag"] = etag.as_str().into();
+        }
+
+        if let Some(last_modified) = &entry.last_modified {
+            meta["lastModified"] = last_modified.as_str().into();
+        }
+
+        write_atomically(&self.index_path(key), json::stringify(meta).as_bytes())
+    }
+}
+
+fn url_key(url: &str) -> String {
+    format!("url:{}", url)
+}
+
+fn isbn_key(isbn: &str) -> String {
+    format!("isbn:{}", isbn)
+}
+
+fn hex_digest(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Number of the temporary files written by this process
+static TMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Writes a file through a temporary one (unique to the process and write),
+/// so a concurrent or interrupted run never sees a partial file.
+fn write_atomically(path: &Path, data: &[u8]) -> Result<()> {
+    let tmp = path.with_extension(format!(
+        "tmp{}-{}", std::process::id(), TMP_FILES.fetch_add(1, Ordering::Relaxed)));
+
+    fs::write(&tmp, data).and_then(|_| fs::rename(&tmp, path))
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = CoverCache::open(dir.path()).unwrap();
+
+        assert_eq!(cache.lookup("http://covers/1", "9782266211963").unwrap(),
+                   None);
+
+        let entry = cache.store(
+            "http://covers/1", "9782266211963", "image/png",
+            Some("\"abc\"".to_string()), None, b"PNG data").unwrap();
+
+        assert_eq!(cache.lookup("http://covers/1", "").unwrap(),
+                   Some(entry.clone()));
+
+        // Same ISBN, other URL
+        assert_eq!(cache.lookup("http://covers/2", "9782266211963").unwrap(),
+                   Some(entry.clone()));
+
+        assert_eq!(cache.lookup_url("http://covers/2").unwrap(), None);
+
+        assert_eq!(cache.read(&entry).unwrap(), b"PNG data".to_vec());
+
+        // Content-addressed: same data, same object
+        let other = cache.store(
+            "http://covers/3", "", "image/png", None, None,
+            b"PNG data").unwrap();
+
+        assert_eq!(other.object, entry.object);
+        assert_eq!(fs::read_dir(dir.path().join("objects")).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_store_concurrently() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = std::sync::Arc::new(CoverCache::open(dir.path()).unwrap());
+
+        let workers: Vec<_> = (0..8).map(|_| {
+            let cache = cache.clone();
+
+            std::thread::spawn(move || (0..20).map(|_| cache.store(
+                "http://covers/placeholder", "", "image/png", None, None,
+                b"PNG data").map(|_| ())).collect::<Result<Vec<()>>>())
+        }).collect();
+
+        for w in workers {
+            assert!(w.join().unwrap().is_ok());
+        }
+
+        let entry = cache.lookup_url("http://covers/placeholder").unwrap().unwrap();
+
+        assert_eq!(cache.read(&entry).unwrap(), b"PNG data".to_vec());
+        assert_eq!(fs::read_dir(dir.path().join("objects")).unwrap().count(), 1);
+    }
+}
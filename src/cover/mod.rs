@@ -0,0 +1,509 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Read, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use reqwest::StatusCode;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{
+    CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    RETRY_AFTER};
+
+pub mod cache;
+pub mod normalize;
+pub mod placeholder;
+pub mod pool;
+pub mod retry;
+pub mod source;
+
+use cache::{CoverCache, Entry};
+use normalize::Normalizer;
+use retry::RetryPolicy;
+use source::CoverSource;
+
+pub const DEFAULT_CONTENT_TYPE: &str = "image/jpeg";
+
+/// Cover image
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cover {
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// How the cover cache is used
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheMode {
+    /// Use the cached covers, fetch only the missing ones
+    Cached,
+    /// Use only the cached covers, without network access
+    Offline,
+    /// Revalidate the cached covers (using ETag/Last-Modified)
+    Refresh,
+}
+
+/// Fetches the cover images, through the cache if any
+/// (for the remote ones).
+pub struct CoverFetcher {
+    http: Client,
+    cache: Option<CoverCache>,
+    mode: CacheMode,
+    rate_limit: Option<RateLimit>,
+    retry: RetryPolicy,
+    base_dir: PathBuf,
+    normalizer: Option<Normalizer>,
+}
+
+impl CoverFetcher {
+    pub fn new(
+        http: Client,
+        cache: Option<CoverCache>,
+        mode: CacheMode,
+    ) -> CoverFetcher {
+        CoverFetcher {
+            http,
+            cache,
+            mode,
+            rate_limit: None,
+            retry: RetryPolicy::default(),
+            base_dir: PathBuf::from("."),
+            normalizer: None,
+        }
+    }
+
+    /// Normalizes the fetched covers (format and dimensions).
+    pub fn normalized(self, normalizer: Normalizer) -> CoverFetcher {
+        CoverFetcher { normalizer: Some(normalizer), ..self }
+    }
+
+    /// Resolves the relative cover paths against the given directory.
+    pub fn relative_to<P: AsRef<Path>>(self, base_dir: P) -> CoverFetcher {
+        CoverFetcher { base_dir: base_dir.as_ref().to_path_buf(), ..self }
+    }
+
+    /// Retries the failed HTTP requests according the given policy.
+    pub fn with_retry(self, retry: RetryPolicy) -> CoverFetcher {
+        CoverFetcher { retry, ..self }
+    }
+
+    /// Limits the HTTP requests to the given number per second for each host
+    /// (no limit if 0).
+    pub fn rate_limited(self, requests_per_sec: u32) -> CoverFetcher {
+        let rate_limit = if requests_per_sec == 0 {
+            None
+        } else {
+            Some(RateLimit {
+                interval: Duration::from_secs(1) / requests_per_sec,
+                next: Mutex::new(HashMap::new()),
+            })
+        };
+
+        CoverFetcher { rate_limit, ..self }
+    }
+
+    /// Returns the cover from the given URL (or path, see `CoverSource`),
+    /// with the ISBN (possibly empty) as secondary cache key.
+    pub fn fetch(&self, url: &str, isbn: &str) -> Result<Cover> {
+        let cover = self.fetch_raw(url, isbn)?;
+
+        match &self.normalizer {
+            Some(n) => n.apply(cover),
+            None => Ok(cover),
+        }
+    }
+
+    fn fetch_raw(&self, url: &str, isbn: &str) -> Result<Cover> {
+        if let Some(local) =
+            CoverSource::parse(url, &self.base_dir)?.read_local() {
+            return local;
+        }
+
+        let cache = match &self.cache {
+            Some(c) => c,
+
+            None if self.mode == CacheMode::Offline => return Err(
+                Error::new(ErrorKind::NotFound, "No cover cache (offline)")),
+
+            None => return self.get(url, None).map(|(cover, _)| cover),
+        };
+
+        let cached = match self.mode {
+            CacheMode::Refresh => cache.lookup_url(url),
+            _ => cache.lookup(url, isbn),
+        }.unwrap_or_else(|cause| {
+            log::warn!(target: "mylib",
+                       "Fails to lookup cover cache for '{}': {}", url, cause);
+
+            None
+        });
+
+        match (self.mode, cached) {
+            (CacheMode::Cached, Some(entry)) |
+            (CacheMode::Offline, Some(entry)) => {
+                log::info!(target: "mylib", "Cached cover: {}", url);
+
+                from_cache(cache, &entry)
+            }
+
+            (CacheMode::Offline, None) => Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Cover not cached (offline): {}", url))),
+
+            (_, cached) => {
+                let (fetched, r) = self.get(url, cached.as_ref())?;
+
+                match cached {
+                    Some(entry) if r.status() == StatusCode::NOT_MODIFIED => {
+                        log::info!(target: "mylib",
+                                   "Cover not modified: {}", url);
+
+                        from_cache(cache, &entry)
+                    }
+
+                    _ => {
+                        if let Err(cause) = cache.store(
+                            url, isbn, &fetched.content_type,
+                            header(&r, ETAG), header(&r, LAST_MODIFIED),
+                            &fetched.data) {
+                            log::warn!(target: "mylib",
+                                       "Fails to cache cover '{}': {}",
+                                       url, cause);
+                        }
+
+                        Ok(fetched)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Gets the cover over HTTP (retried according the policy),
+    /// conditionally if a cached entry is given.
+    fn get(
+        &self,
+        url: &str,
+        cached: Option<&Entry>,
+    ) -> Result<(Cover, Response)> {
+        let mut retry: u32 = 0;
+
+        loop {
+            let failure = match self.get_once(url, cached) {
+                Ok(res) => return Ok(res),
+                Err(f) => f,
+            };
+
+            retry += 1;
+
+            let delay = if failure.retryable {
+                self.retry.delay(retry, failure.retry_after)
+            } else {
+                None
+            };
+
+            match delay {
+                Some(d) => {
+                    log::info!(target: "mylib",
+                               "Retry cover '{}' in {:?} (attempt #{}): {}",
+                               url, d, retry + 1, failure.cause);
+
+                    std::thread::sleep(d);
+                }
+
+                None => return Err(failure.cause),
+            }
+        }
+    }
+
+    fn get_once(
+        &self,
+        url: &str,
+        cached: Option<&Entry>,
+    ) -> std::result::Result<(Cover, Response), Failure> {
+        if let Some(limit) = &self.rate_limit {
+            limit.wait(url);
+        }
+
+        let mut req = self.http.get(url);
+
+        if let Some(entry) = cached {
+            if let Some(etag) = &entry.etag {
+                req = req.header(IF_NONE_MATCH, etag.as_str());
+            }
+
+            if let Some(last_modified) = &entry.last_modified {
+                req = req.header(IF_MODIFIED_SINCE, last_modified.as_str());
+            }
+        }
+
+        let mut r = req.send().map_err(|cause| Failure {
+            cause: Error::new(ErrorKind::Interrupted, cause),
+            retryable: true,
+            retry_after: None,
+        })?;
+
+        if r.status() == StatusCode::NOT_MODIFIED && cached.is_some() {
+            let cover = Cover {
+                content_type: DEFAULT_CONTENT_TYPE.to_string(),
+                data: vec![],
+            };
+
+            return Ok((cover, r));
+        }
+
+        let status = r.status();
+
+        if !status.is_success() {
+            let kind = if status == StatusCode::NOT_FOUND ||
+                status == StatusCode::GONE {
+                ErrorKind::NotFound
+            } else if status == StatusCode::TOO_MANY_REQUESTS ||
+                status.is_server_error() {
+                ErrorKind::Interrupted
+            } else {
+                ErrorKind::Other
+            };
+
+            return Err(Failure {
+                cause: Error::new(
+                    kind, format!("Fails to get cover ({}): {}", status, url)),
+                retryable: status == StatusCode::TOO_MANY_REQUESTS ||
+                    status.is_server_error(),
+                retry_after: header(&r, RETRY_AFTER).
+                    and_then(|v| retry::parse_retry_after(&v)),
+            });
+        }
+
+        let mut data: Vec<u8> = vec![];
+
+        r.read_to_end(&mut data).map_err(|cause| Failure {
+            cause,
+            retryable: true,
+            retry_after: None,
+        })?;
+
+        if data.is_empty() {
+            return Err(Failure {
+                cause: Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Missing cover data: {}", url)),
+                retryable: false,
+                retry_after: None,
+            });
+        }
+
+        let content_type = header(&r, CONTENT_TYPE).unwrap_or_else(|| {
+            log::warn!(target: "mylib",
+                       "Fails to determine type for cover '{}'", url);
+
+            DEFAULT_CONTENT_TYPE.to_string()
+        });
+
+        Ok((Cover { content_type, data }, r))
+    }
+}
+
+/// Failed attempt to get a cover
+struct Failure {
+    cause: Error,
+    /// Whether another attempt may succeed (network or server error)
+    retryable: bool,
+    /// Delay required by the server before another attempt
+    retry_after: Option<Duration>,
+}
+
+/// Per-host rate limit, shared by the concurrent fetches.
+struct RateLimit {
+    interval: Duration,
+    /// Next instant a request is allowed, by host
+    next: Mutex<HashMap<String, Instant>>,
+}
+
+impl RateLimit {
+    /// Waits until a request to the host of the URL is allowed.
+    fn wait(&self, url: &str) {
+        let host = reqwest::Url::parse(url).ok().
+            and_then(|u| u.host_str().map(|h| h.to_string())).
+            unwrap_or_default();
+
+        let delay = {
+            let mut next = self.next.lock().
+                unwrap_or_else(|poisoned| poisoned.into_inner());
+
+            let now = Instant::now();
+            let at = next.get(&host).map_or(now, |n| (*n).max(now));
+
+            next.insert(host, at + self.interval);
+
+            at - now
+        };
+
+        if delay > Duration::from_millis(0) {
+            std::thread::sleep(delay);
+        }
+    }
+}
+
+fn from_cache(cache: &CoverCache, entry: &Entry) -> Result<Cover> {
+    cache.read(entry).map(|data| Cover {
+        content_type: entry.content_type.to_string(),
+        data,
+    })
+}
+
+fn header(r: &Response, name: reqwest::header::HeaderName) -> Option<String> {
+    r.headers().get(name).
+        and_then(|v| v.to_str().ok()).
+        map(|v| v.to_string())
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fetch_offline() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = CoverCache::open(dir.path()).unwrap();
+
+        cache.store("http://covers.invalid/1", "9782266211963", "image/png",
+                    None, None, b"PNG data").unwrap();
+
+        let fetcher = CoverFetcher::new(
+            Client::new(), Some(cache), CacheMode::Offline);
+
+        assert_eq!(fetcher.fetch("http://covers.invalid/1", "").unwrap(),
+                   Cover {
+                       content_type: "image/png".to_string(),
+                       data: b"PNG data".to_vec(),
+                   });
+
+        // Found by ISBN
+        assert_eq!(fetcher.fetch("http://covers.invalid/2", "9782266211963").
+                   unwrap().data, b"PNG data".to_vec());
+
+        assert_eq!(fetcher.fetch("http://covers.invalid/3", "").
+                   unwrap_err().kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_fetch_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = CoverCache::open(dir.path()).unwrap();
+
+        cache.store("http://covers.invalid/1", "", "image/jpeg",
+                    None, None, b"JPEG data").unwrap();
+
+        let fetcher = CoverFetcher::new(
+            Client::new(), Some(cache), CacheMode::Cached);
+
+        // No network access as cached
+        assert_eq!(fetcher.fetch("http://covers.invalid/1", "").unwrap().data,
+                   b"JPEG data".to_vec());
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let limit = RateLimit {
+            interval: Duration::from_millis(50),
+            next: Mutex::new(HashMap::new()),
+        };
+
+        let start = Instant::now();
+
+        limit.wait("http://a.invalid/1");
+        limit.wait("http://b.invalid/1");
+
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        limit.wait("http://a.invalid/2");
+        limit.wait("http://a.invalid/3");
+
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    /// Serves the given HTTP responses, one per connection.
+    fn serve(responses: Vec<&'static str>) -> String {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+
+                // Skip request headers
+                while let Some(Ok(line)) = lines.next() {
+                    if line.is_empty() {
+                        break;
+                    }
+                }
+
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        format!("http://{}/cover.jpg", addr)
+    }
+
+    #[test]
+    fn test_fetch_retry() {
+        let url = serve(vec![
+            "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Type: image/jpeg\r\nContent-Length: 4\r\nConnection: close\r\n\r\nJPEG",
+        ]);
+
+        let fetcher = CoverFetcher::new(Client::new(), None, CacheMode::Cached).
+            with_retry(RetryPolicy {
+                max_attempts: 3,
+                base_delay: Duration::from_millis(10),
+                max_delay: Duration::from_secs(1),
+            });
+
+        assert_eq!(fetcher.fetch(&url, "").unwrap(), Cover {
+            content_type: "image/jpeg".to_string(),
+            data: b"JPEG".to_vec(),
+        });
+    }
+
+    #[test]
+    fn test_fetch_no_retry() {
+        let url = serve(vec![
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Type: image/jpeg\r\nContent-Length: 4\r\nConnection: close\r\n\r\nJPEG",
+        ]);
+
+        let fetcher = CoverFetcher::new(Client::new(), None, CacheMode::Cached);
+
+        // Not found is not retried
+        assert_eq!(fetcher.fetch(&url, "").unwrap_err().kind(),
+                   ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_fetch_local() {
+        let dir = tempfile::tempdir().unwrap();
+
+        std::fs::write(dir.path().join("1.png"), b"PNG data").unwrap();
+
+        // Local covers are available offline, without cache
+        let fetcher = CoverFetcher::new(Client::new(), None, CacheMode::Offline).
+            relative_to(dir.path());
+
+        assert_eq!(fetcher.fetch("1.png", "").unwrap().data,
+                   b"PNG data".to_vec());
+
+        let url = format!("file://{}", dir.path().join("1.png").display());
+
+        assert_eq!(fetcher.fetch(&url, "").unwrap().data, b"PNG data".to_vec());
+
+        assert_eq!(fetcher.fetch("data:,PNG%20data", "").unwrap(), Cover {
+            content_type: DEFAULT_CONTENT_TYPE.to_string(),
+            data: b"PNG data".to_vec(),
+        });
+    }
+}
@@ -0,0 +1,352 @@
+use std::io::{Cursor, Error, ErrorKind, Result};
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageReader, Rgb, RgbImage};
+
+use super::Cover;
+use super::placeholder::{self, Placeholder, PlaceholderSet};
+
+pub const DEFAULT_MAX_DIMENSION: u32 = 800;
+
+/// Covers with a smaller width or height are considered as placeholders
+/// (e.g. the 1x1 "image not available" returned by some providers).
+pub const DEFAULT_MIN_DIMENSION: u32 = 16;
+
+const JPEG_QUALITY: u8 = 85;
+
+/// Image format, as detected from the magic bytes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Jpeg,
+    Png,
+    Gif,
+    WebP,
+    Avif,
+}
+
+impl Format {
+    /// Detects the image format from the first bytes of the data.
+    pub fn sniff(data: &[u8]) -> Option<Format> {
+        if data.starts_with(b"\xFF\xD8\xFF") {
+            Some(Format::Jpeg)
+        } else if data.starts_with(b"\x89PNG\r\n\x1A\n") {
+            Some(Format::Png)
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(Format::Gif)
+        } else if data.len() >= 12 &&
+            &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+            Some(Format::WebP)
+        } else if data.len() >= 12 && &data[4..8] == b"ftyp" &&
+            (&data[8..12] == b"avif" || &data[8..12] == b"avis") {
+            Some(Format::Avif)
+        } else {
+            None
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Jpeg => "image/jpeg",
+            Format::Png => "image/png",
+            Format::Gif => "image/gif",
+            Format::WebP => "image/webp",
+            Format::Avif => "image/avif",
+        }
+    }
+}
+
+/// Normalizes the covers for Mylib (JPEG or PNG, with bounded dimensions).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Normalizer {
+    /// Maximum width or height (larger covers are downscaled)
+    pub max_dimension: u32,
+    /// Minimum width and height (smaller covers are rejected as placeholders)
+    pub min_dimension: u32,
+    /// Known placeholder images (rejected)
+    pub placeholders: PlaceholderSet,
+}
+
+impl Default for Normalizer {
+    fn default() -> Normalizer {
+        Normalizer {
+            max_dimension: DEFAULT_MAX_DIMENSION,
+            min_dimension: DEFAULT_MIN_DIMENSION,
+            placeholders: PlaceholderSet::bundled(),
+        }
+    }
+}
+
+impl Normalizer {
+    /// Returns the cover as JPEG or PNG (transcoded and downscaled if needed),
+    /// whatever its declared content type.
+    ///
+    /// A placeholder image is rejected with a `Placeholder` error.
+    pub fn apply(&self, cover: Cover) -> Result<Cover> {
+        let format = Format::sniff(&cover.data).ok_or_else(|| Error::new(
+            ErrorKind::InvalidData,
+            format!("Unknown cover image format (declared as {})",
+                    cover.content_type)))?;
+
+        let image_format = match format {
+            Format::Jpeg => ImageFormat::Jpeg,
+            Format::Png => ImageFormat::Png,
+            Format::Gif => ImageFormat::Gif,
+            Format::WebP => ImageFormat::WebP,
+
+            // Decoding AV1 requires the native dav1d library (`image` feature
+            // `avif-native`), which is not a dependency of this build
+            Format::Avif => return Err(Error::new(
+                ErrorKind::Unsupported,
+                "AVIF cover cannot be transcoded (no AV1 decoder)")),
+        };
+
+        let reader = || ImageReader::with_format(
+            Cursor::new(&cover.data), image_format);
+
+        let (width, height) = reader().into_dimensions().
+            map_err(|cause| Error::new(ErrorKind::InvalidData, cause))?;
+
+        if width < self.min_dimension || height < self.min_dimension {
+            return Err(Placeholder(format!("{}x{}", width, height)).into());
+        }
+
+        let oversized =
+            width > self.max_dimension || height > self.max_dimension;
+
+        let kept = !oversized &&
+            (format == Format::Jpeg || format == Format::Png);
+
+        let mut img = reader().decode().
+            map_err(|cause| Error::new(ErrorKind::InvalidData, cause))?;
+
+        if placeholder::is_blank(&img) {
+            return Err(Placeholder("blank image".to_string()).into());
+        }
+
+        if self.placeholders.matches(&img) {
+            return Err(Placeholder("known image".to_string()).into());
+        }
+
+        if kept {
+            return Ok(Cover {
+                content_type: format.content_type().to_string(),
+                data: cover.data,
+            });
+        }
+
+        if oversized {
+            img = img.resize(
+                self.max_dimension, self.max_dimension, FilterType::Triangle);
+        }
+
+        log::info!(target: "mylib", "Normalized {:?} cover from {}x{} to {}x{}",
+                   format, width, height, img.width(), img.height());
+
+        let mut data = vec![];
+
+        if format == Format::Png {
+            img.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+        } else {
+            flatten(&img).write_with_encoder(
+                JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY))
+        }.map_err(Error::other)?;
+
+        let content_type = if format == Format::Png {
+            Format::Png
+        } else {
+            Format::Jpeg
+        }.content_type().to_string();
+
+        Ok(Cover { content_type, data })
+    }
+}
+
+/// Returns the image as RGB, with the transparent pixels on white
+/// (as JPEG has no alpha channel).
+fn flatten(img: &DynamicImage) -> RgbImage {
+    let rgba = img.to_rgba8();
+
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend = |c: u8| {
+            ((c as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8
+        };
+
+        Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{Rgba, RgbaImage};
+
+    /// Encodes a picture (not a blank placeholder)
+    fn encode(width: u32, height: u32, format: ImageFormat) -> Vec<u8> {
+        let img = RgbaImage::from_fn(width, height, |x, _| {
+            Rgba([200, 10, (255 - x * 255 / width) as u8, 255])
+        });
+        let mut data = vec![];
+
+        let img = if format == ImageFormat::Jpeg {
+            DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(img).to_rgb8())
+        } else {
+            DynamicImage::ImageRgba8(img)
+        };
+
+        img.write_to(&mut Cursor::new(&mut data), format).unwrap();
+
+        data
+    }
+
+    fn dimensions(cover: &Cover) -> (u32, u32) {
+        image::load_from_memory(&cover.data).unwrap().into_rgb8().dimensions()
+    }
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(Format::sniff(&encode(2, 2, ImageFormat::Jpeg)),
+                   Some(Format::Jpeg));
+        assert_eq!(Format::sniff(&encode(2, 2, ImageFormat::Png)),
+                   Some(Format::Png));
+        assert_eq!(Format::sniff(&encode(2, 2, ImageFormat::Gif)),
+                   Some(Format::Gif));
+        assert_eq!(Format::sniff(b"RIFF\0\0\0\0WEBPVP8 "), Some(Format::WebP));
+        assert_eq!(Format::sniff(b"\0\0\0\x1CftypavifXXXX"),
+                   Some(Format::Avif));
+        assert_eq!(Format::sniff(b"<html>"), None);
+    }
+
+    #[test]
+    fn test_apply() {
+        let n = Normalizer {
+            max_dimension: 100,
+            ..Default::default()
+        };
+
+        // Kept as is, whatever the declared type
+        let png = encode(40, 60, ImageFormat::Png);
+        let cover = n.apply(Cover {
+            content_type: "application/octet-stream".to_string(),
+            data: png.clone(),
+        }).unwrap();
+
+        assert_eq!(cover, Cover {
+            content_type: "image/png".to_string(),
+            data: png,
+        });
+
+        // Transcoded to JPEG
+        let cover = n.apply(Cover {
+            content_type: "image/gif".to_string(),
+            data: encode(40, 60, ImageFormat::Gif),
+        }).unwrap();
+
+        assert_eq!(cover.content_type, "image/jpeg");
+        assert_eq!(Format::sniff(&cover.data), Some(Format::Jpeg));
+        assert_eq!(dimensions(&cover), (40, 60));
+
+        let cover = n.apply(Cover {
+            content_type: "image/webp".to_string(),
+            data: encode(40, 60, ImageFormat::WebP),
+        }).unwrap();
+
+        assert_eq!(cover.content_type, "image/jpeg");
+        assert_eq!(Format::sniff(&cover.data), Some(Format::Jpeg));
+        assert_eq!(dimensions(&cover), (40, 60));
+
+        // Downscaled
+        let cover = n.apply(Cover {
+            content_type: "image/jpeg".to_string(),
+            data: encode(300, 150, ImageFormat::Jpeg),
+        }).unwrap();
+
+        assert_eq!(cover.content_type, "image/jpeg");
+        assert_eq!(dimensions(&cover), (100, 50));
+    }
+
+    #[test]
+    fn test_apply_rejected() {
+        let n = Normalizer::default();
+
+        // 1x1 placeholder
+        let err = n.apply(Cover {
+            content_type: "image/gif".to_string(),
+            data: encode(1, 1, ImageFormat::Gif),
+        }).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(crate::cover::placeholder::is_placeholder(&err));
+
+        // Not an image
+        assert_eq!(n.apply(Cover {
+            content_type: "image/jpeg".to_string(),
+            data: b"<html>Not found</html>".to_vec(),
+        }).unwrap_err().kind(), ErrorKind::InvalidData);
+
+        assert_eq!(n.apply(Cover {
+            content_type: "image/avif".to_string(),
+            data: b"\0\0\0\x1CftypavifXXXX".to_vec(),
+        }).unwrap_err().kind(), ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn test_apply_known_placeholder() {
+        let dir = tempfile::tempdir().unwrap();
+        let png = encode(40, 60, ImageFormat::Png);
+
+        std::fs::write(dir.path().join("placeholder.png"), &png).unwrap();
+
+        let mut n = Normalizer::default();
+
+        n.placeholders.add_dir(dir.path()).unwrap();
+
+        let err = n.apply(Cover {
+            content_type: "image/jpeg".to_string(),
+            data: encode(80, 120, ImageFormat::Jpeg),
+        }).unwrap_err();
+
+        assert!(crate::cover::placeholder::is_placeholder(&err));
+    }
+
+    #[test]
+    fn test_apply_gradient() {
+        // Brightening left to right, as a dHash of zero
+        let img = RgbImage::from_fn(128, 192, |x, _| {
+            let v = (x * 255 / 128) as u8;
+
+            Rgb([v, v, v])
+        });
+        let mut data = vec![];
+
+        DynamicImage::ImageRgb8(img).
+            write_to(&mut Cursor::new(&mut data), ImageFormat::Jpeg).unwrap();
+
+        let cover = Normalizer::default().apply(Cover {
+            content_type: "image/jpeg".to_string(),
+            data: data.clone(),
+        }).unwrap();
+
+        assert_eq!(cover.data, data);
+    }
+
+    #[test]
+    fn test_apply_blank() {
+        let blank = RgbImage::from_pixel(128, 192, Rgb([255, 255, 255]));
+        let mut data = vec![];
+
+        DynamicImage::ImageRgb8(blank).
+            write_to(&mut Cursor::new(&mut data), ImageFormat::Jpeg).unwrap();
+
+        let err = Normalizer::default().apply(Cover {
+            content_type: "image/jpeg".to_string(),
+            data,
+        }).unwrap_err();
+
+        assert!(crate::cover::placeholder::is_placeholder(&err));
+    }
+}
@@ -0,0 +1,193 @@
+use std::fmt;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use image::DynamicImage;
+use image::imageops::FilterType;
+
+/// Hashes of the known placeholder covers (see the file header)
+const BUNDLED: &str = include_str!("../../resources/placeholders.txt");
+
+/// Maximum number of different bits for two images to be considered the same
+const MAX_DISTANCE: u32 = 6;
+
+/// Maximum difference of brightness within an image considered as blank
+const MAX_BLANK_RANGE: u8 = 8;
+
+/// Error for a placeholder image, rather than an actual cover
+#[derive(Debug)]
+pub struct Placeholder(pub String);
+
+impl fmt::Display for Placeholder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Placeholder cover ({})", self.0)
+    }
+}
+
+impl std::error::Error for Placeholder {}
+
+impl From<Placeholder> for Error {
+    fn from(p: Placeholder) -> Error {
+        Error::new(ErrorKind::InvalidData, p)
+    }
+}
+
+/// Whether the error is about a placeholder cover.
+pub fn is_placeholder(cause: &Error) -> bool {
+    cause.get_ref().is_some_and(|e| e.is::<Placeholder>())
+}
+
+/// Perceptual hashes of the known placeholder images
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaceholderSet {
+    hashes: Vec<u64>,
+}
+
+impl Default for PlaceholderSet {
+    fn default() -> PlaceholderSet {
+        PlaceholderSet::bundled()
+    }
+}
+
+impl PlaceholderSet {
+    /// Returns the bundled set of placeholders.
+    pub fn bundled() -> PlaceholderSet {
+        let hashes = BUNDLED.lines().filter_map(|line| {
+            let hash = line.split('#').next()?.split_whitespace().next()?;
+
+            u64::from_str_radix(hash, 16).map_err(|cause| log::warn!(
+                target: "mylib", "Invalid placeholder hash '{}': {}", hash, cause)).ok()
+        }).collect();
+
+        PlaceholderSet { hashes }
+    }
+
+    /// Adds the images of the given directory as placeholders.
+    pub fn add_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            match image::open(&path) {
+                Ok(img) => {
+                    let hash = dhash(&img);
+
+                    log::info!(target: "mylib", "Placeholder {:016x}: {}",
+                               hash, path.display());
+
+                    self.hashes.push(hash);
+                }
+
+                Err(cause) => log::warn!(
+                    target: "mylib", "Skip placeholder '{}': {}", path.display(), cause),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Whether the image looks like one of the placeholders.
+    pub fn matches(&self, img: &DynamicImage) -> bool {
+        let hash = dhash(img);
+
+        self.hashes.iter().any(|h| (h ^ hash).count_ones() <= MAX_DISTANCE)
+    }
+}
+
+/// Whether the image is blank (of a single color, give or take some noise),
+/// judging by the brightness of a 16x16 grayscale thumbnail.
+pub fn is_blank(img: &DynamicImage) -> bool {
+    let thumb = img.resize_exact(16, 16, FilterType::Triangle).to_luma8();
+
+    let (min, max) = thumb.pixels().fold((u8::MAX, u8::MIN), |(min, max), p| {
+        (min.min(p.0[0]), max.max(p.0[0]))
+    });
+
+    max - min <= MAX_BLANK_RANGE
+}
+
+/// Returns the difference hash of the image:
+/// one bit per pair of horizontally adjacent pixels in a 9x8 grayscale
+/// thumbnail, set if the left one is brighter.
+fn dhash(img: &DynamicImage) -> u64 {
+    let thumb = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+
+    (0..8).flat_map(|y| (0..8).map(move |x| (x, y))).fold(0, |hash, (x, y)| {
+        let bit = thumb.get_pixel(x, y).0[0] > thumb.get_pixel(x + 1, y).0[0];
+
+        (hash << 1) | bit as u64
+    })
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{Luma, GrayImage};
+
+    /// Horizontal gradient, left to right or right to left
+    fn gradient(width: u32, height: u32, reverse: bool) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, _| {
+            let v = (x * 255 / width) as u8;
+
+            Luma([if reverse { 255 - v } else { v }])
+        }))
+    }
+
+    #[test]
+    fn test_matches() {
+        let mut set = PlaceholderSet { hashes: vec![] };
+
+        assert!(!set.matches(&gradient(128, 192, false)));
+
+        let dir = tempfile::tempdir().unwrap();
+
+        gradient(128, 192, false).save(dir.path().join("p.png")).unwrap();
+        fs::write(dir.path().join("README"), "Not an image").unwrap();
+
+        set.add_dir(dir.path()).unwrap();
+
+        assert_eq!(set.hashes.len(), 1);
+
+        // Same picture, other size
+        assert!(set.matches(&gradient(256, 300, false)));
+
+        assert!(!set.matches(&gradient(128, 192, true)));
+    }
+
+    #[test]
+    fn test_bundled() {
+        let entries = BUNDLED.lines().
+            filter(|line| !line.trim().is_empty() && !line.starts_with('#')).
+            count();
+
+        assert_eq!(PlaceholderSet::bundled().hashes.len(), entries);
+    }
+
+    #[test]
+    fn test_is_blank() {
+        let blank = GrayImage::from_pixel(128, 192, Luma([230]));
+
+        assert!(is_blank(&DynamicImage::ImageLuma8(blank)));
+
+        let noisy = GrayImage::from_fn(128, 192, |x, y| Luma([230 + ((x + y) % 3) as u8]));
+
+        assert!(is_blank(&DynamicImage::ImageLuma8(noisy)));
+
+        // Brightening left to right (all the dHash bits unset)
+        assert!(!is_blank(&gradient(128, 192, false)));
+        assert!(!is_blank(&gradient(128, 192, true)));
+    }
+
+    #[test]
+    fn test_is_placeholder() {
+        assert!(is_placeholder(&Placeholder("1x1".to_string()).into()));
+        assert!(!is_placeholder(&Error::new(ErrorKind::InvalidData, "1x1")));
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use super::{Cover, CoverFetcher};
+
+/// Number of items read ahead for each worker
+const WINDOW_PER_WORKER: usize = 4;
+
+/// Receiver of a cover fetched by a worker
+type Reply = Receiver<Result<Cover>>;
+
+struct Job {
+    url: String,
+    isbn: String,
+    reply: Sender<Result<Cover>>,
+}
+
+/// Iterator adapter fetching the covers of the source items
+/// with a bounded pool of workers.
+///
+/// The items are yielded in the source order, each with its cover
+/// (if `key` returned some URL and ISBN for it),
+/// while the covers of the next items are fetched concurrently.
+pub struct Prefetch<I: Iterator, F> {
+    source: I,
+    key: F,
+    window: usize,
+    pending: VecDeque<(I::Item, Option<Reply>)>,
+    jobs: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+/// Returns the source items with their covers,
+/// fetched using at most `concurrency` workers.
+pub fn prefetch<I, F>(
+    source: I,
+    key: F,
+    fetcher: Arc<CoverFetcher>,
+    concurrency: usize,
+) -> Prefetch<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item) -> Option<(String, String)>,
+{
+    let concurrency = concurrency.max(1);
+    let (jobs, queue) = channel::<Job>();
+    let queue = Arc::new(Mutex::new(queue));
+
+    let workers = (0..concurrency).map(|_| {
+        let queue = queue.clone();
+        let fetcher = fetcher.clone();
+
+        std::thread::spawn(move || loop {
+            let next = queue.lock().
+                unwrap_or_else(|poisoned| poisoned.into_inner()).recv();
+
+            match next {
+                Ok(job) => {
+                    // Receiver may be gone if the iteration is stopped early
+                    let _ = job.reply.send(fetcher.fetch(&job.url, &job.isbn));
+                }
+
+                Err(_) => break, // no more job
+            }
+        })
+    }).collect();
+
+    Prefetch {
+        source,
+        key,
+        window: concurrency * WINDOW_PER_WORKER,
+        pending: VecDeque::new(),
+        jobs: Some(jobs),
+        workers,
+    }
+}
+
+impl<I, F> Iterator for Prefetch<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item) -> Option<(String, String)>,
+{
+    type Item = (I::Item, Option<Result<Cover>>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.len() < self.window {
+            match self.source.next() {
+                Some(item) => {
+                    let reply = (self.key)(&item).and_then(|(url, isbn)| {
+                        let (reply, rx) = channel();
+
+                        self.jobs.as_ref().
+                            and_then(|jobs| jobs.send(
+                                Job { url, isbn, reply }).ok()).
+                            map(|_| rx)
+                    });
+
+                    self.pending.push_back((item, reply));
+                }
+
+                None => break,
+            }
+        }
+
+        self.pending.pop_front().map(|(item, reply)| {
+            let cover = reply.map(|rx| rx.recv().unwrap_or_else(
+                |_| Err(Error::new(
+                    ErrorKind::Interrupted, "Cover worker stopped"))));
+
+            (item, cover)
+        })
+    }
+}
+
+impl<I: Iterator, F> Drop for Prefetch<I, F> {
+    fn drop(&mut self) {
+        // Closing the job queue stops the workers
+        self.jobs.take();
+
+        for w in self.workers.drain(..) {
+            let _ = w.join();
+        }
+    }
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cover::CacheMode;
+    use crate::cover::cache::CoverCache;
+
+    #[test]
+    fn test_prefetch_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = CoverCache::open(dir.path()).unwrap();
+
+        for i in 0..20 {
+            cache.store(&format!("http://covers.invalid/{}", i), "",
+                        "image/png", None, None,
+                        format!("data #{}", i).as_bytes()).unwrap();
+        }
+
+        let fetcher = Arc::new(CoverFetcher::new(
+            reqwest::blocking::Client::new(), Some(cache), CacheMode::Offline));
+
+        let items: Vec<(u32, Option<Vec<u8>>)> = prefetch(
+            0..25,
+            |i| if i % 3 == 0 {
+                None
+            } else {
+                Some((format!("http://covers.invalid/{}", i), "".to_string()))
+            },
+            fetcher,
+            3).map(|(i, cover)| (i, cover.and_then(|c| c.ok()).map(|c| c.data))).
+            collect();
+
+        assert_eq!(items.len(), 25);
+
+        for (n, (i, cover)) in items.into_iter().enumerate() {
+            assert_eq!(n as u32, i);
+
+            let expected = if i % 3 == 0 || i >= 20 {
+                None
+            } else {
+                Some(format!("data #{}", i).into_bytes())
+            };
+
+            assert_eq!(cover, expected);
+        }
+    }
+}
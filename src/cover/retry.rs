@@ -0,0 +1,133 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, SystemTime};
+
+/// Retry policy for the cover downloads,
+/// with exponential backoff and jitter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts (including the first one)
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each next one
+    pub base_delay: Duration,
+    /// Maximum delay before a retry
+    /// (a server requiring a longer `Retry-After` is not retried)
+    pub max_delay: Duration,
+}
+
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+pub const DEFAULT_BASE_DELAY: Duration = Duration::from_millis(500);
+pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: DEFAULT_BASE_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay before the given retry (starting from 1),
+    /// or `None` if no more attempt is allowed.
+    pub fn delay(
+        &self,
+        retry: u32,
+        retry_after: Option<Duration>,
+    ) -> Option<Duration> {
+        if retry >= self.max_attempts {
+            return None;
+        }
+
+        match retry_after {
+            Some(d) if d > self.max_delay => None,
+
+            Some(d) => Some(d),
+
+            None => {
+                let exp = self.base_delay.
+                    saturating_mul(1 << (retry - 1).min(16)).
+                    min(self.max_delay);
+
+                // "Equal jitter": half fixed, half random
+                let half = exp / 2;
+
+                Some(half + half.mul_f64(jitter()))
+            }
+        }
+    }
+}
+
+/// Parses a `Retry-After` header value (delay in seconds or HTTP date).
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    let v = value.trim();
+
+    v.parse::<u64>().map(Duration::from_secs).ok().or_else(|| {
+        httpdate::parse_http_date(v).ok().map(
+            |at| at.duration_since(SystemTime::now()).unwrap_or_default())
+    })
+}
+
+/// Returns a random factor in [0, 1].
+fn jitter() -> f64 {
+    RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(300),
+        };
+
+        let d1 = policy.delay(1, None).unwrap();
+
+        assert!(d1 >= Duration::from_millis(50) &&
+                d1 <= Duration::from_millis(100));
+
+        let d2 = policy.delay(2, None).unwrap();
+
+        assert!(d2 >= Duration::from_millis(100) &&
+                d2 <= Duration::from_millis(200));
+
+        // Capped to max delay
+        let d3 = policy.delay(3, None).unwrap();
+
+        assert!(d3 >= Duration::from_millis(150) &&
+                d3 <= Duration::from_millis(300));
+
+        assert_eq!(policy.delay(4, None), None);
+
+        assert_eq!(policy.delay(1, Some(Duration::from_millis(250))),
+                   Some(Duration::from_millis(250)));
+
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(1))), None);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after(" 0 "), Some(Duration::from_secs(0)));
+
+        // Date in the past
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+                   Some(Duration::from_secs(0)));
+
+        let later = httpdate::fmt_http_date(
+            SystemTime::now() + Duration::from_secs(3600));
+
+        assert!(parse_retry_after(&later).unwrap() >
+                Duration::from_secs(3500));
+
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+}
@@ -0,0 +1,219 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use super::{Cover, DEFAULT_CONTENT_TYPE};
+
+/// Where a cover image is read from
+#[derive(Debug, Clone, PartialEq)]
+pub enum CoverSource {
+    /// Remote image (`http://` or `https://` URL)
+    Http(String),
+    /// Local file (`file://` URL, or path)
+    File(PathBuf),
+    /// Embedded image (`data:` URI)
+    Data(Cover),
+}
+
+impl CoverSource {
+    /// Parses a cover reference,
+    /// resolving the relative paths against the base directory
+    /// (e.g. the directory of the input file).
+    pub fn parse(reference: &str, base_dir: &Path) -> Result<CoverSource> {
+        let r = reference.trim();
+        let scheme = r.split_once(':').map(|(s, _)| s.to_ascii_lowercase());
+
+        match scheme.as_deref() {
+            Some("http") | Some("https") => Ok(CoverSource::Http(r.to_string())),
+
+            Some("file") => file_path(r).map(CoverSource::File),
+
+            Some("data") => data_uri(r).map(CoverSource::Data),
+
+            // Other scheme (a single letter being rather a Windows drive)
+            Some(s) if s.len() > 1 && is_scheme(s) => Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("Unsupported cover source: {}", r))),
+
+            _ => Ok(CoverSource::File(base_dir.join(r))),
+        }
+    }
+
+    /// Reads the cover from a local source (`None` for a remote one).
+    pub fn read_local(&self) -> Option<Result<Cover>> {
+        match self {
+            CoverSource::Http(_) => None,
+
+            CoverSource::File(path) => Some(fs::read(path).and_then(|data| {
+                if data.is_empty() {
+                    Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Empty cover file: {}", path.display())))
+                } else {
+                    Ok(Cover { content_type: file_type(path), data })
+                }
+            })),
+
+            CoverSource::Data(cover) => Some(Ok(cover.clone())),
+        }
+    }
+}
+
+fn is_scheme(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic()) &&
+        s.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+}
+
+/// Returns the path of a `file://` URL (`file:///path` or `file://localhost/path`).
+fn file_path(url: &str) -> Result<PathBuf> {
+    let rest = &url["file:".len()..];
+
+    let path = match rest.strip_prefix("//") {
+        Some(authority) => match authority.find('/') {
+            Some(0) => authority,
+            Some(i) if &authority[..i] == "localhost" => &authority[i..],
+            _ => return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("Unsupported cover host: {}", url))),
+        },
+
+        None => rest,
+    };
+
+    percent_decode(path).and_then(|p| String::from_utf8(p).ok()).
+        map(PathBuf::from).ok_or_else(|| Error::new(
+        ErrorKind::InvalidInput,
+        format!("Invalid cover URL: {}", url)))
+}
+
+/// Decodes a `data:[<type>][;base64],<data>` URI.
+fn data_uri(uri: &str) -> Result<Cover> {
+    let invalid = || Error::new(
+        ErrorKind::InvalidData,
+        format!("Invalid cover data URI: {}", crate::codex::ellipsis(uri, 32)));
+
+    let (meta, payload) = uri["data:".len()..].split_once(',').
+        ok_or_else(invalid)?;
+
+    let (media_type, base64) = match meta.strip_suffix(";base64") {
+        Some(t) => (t, true),
+        None => (meta, false),
+    };
+
+    let content_type = media_type.split(';').next().
+        filter(|t| !t.is_empty()).
+        unwrap_or(DEFAULT_CONTENT_TYPE).to_string();
+
+    let data = if base64 {
+        base64::decode(payload.trim()).map_err(|_| invalid())?
+    } else {
+        percent_decode(payload).ok_or_else(invalid)?
+    };
+
+    if data.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok(Cover { content_type, data })
+}
+
+/// Returns the image type according the file extension.
+fn file_type(path: &Path) -> String {
+    let ext = path.extension().and_then(|e| e.to_str()).
+        map(|e| e.to_ascii_lowercase());
+
+    match ext.as_deref() {
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("avif") => "image/avif",
+        _ => DEFAULT_CONTENT_TYPE,
+    }.to_string()
+}
+
+fn percent_decode(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut it = s.bytes();
+
+    while let Some(b) = it.next() {
+        if b == b'%' {
+            let hex = [it.next()?, it.next()?];
+            let h = std::str::from_utf8(&hex).ok()?;
+
+            bytes.push(u8::from_str_radix(h, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+
+    Some(bytes)
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let base = Path::new("/backup");
+
+        assert_eq!(CoverSource::parse("https://covers/1.jpg", base).unwrap(),
+                   CoverSource::Http("https://covers/1.jpg".to_string()));
+
+        assert_eq!(CoverSource::parse("file:///tmp/My%20cover.png", base).
+                   unwrap(),
+                   CoverSource::File(PathBuf::from("/tmp/My cover.png")));
+
+        assert_eq!(CoverSource::parse("file://localhost/tmp/1.png", base).
+                   unwrap(),
+                   CoverSource::File(PathBuf::from("/tmp/1.png")));
+
+        assert_eq!(CoverSource::parse("covers/1.png", base).unwrap(),
+                   CoverSource::File(PathBuf::from("/backup/covers/1.png")));
+
+        assert_eq!(CoverSource::parse("/covers/1.png", base).unwrap(),
+                   CoverSource::File(PathBuf::from("/covers/1.png")));
+
+        assert_eq!(CoverSource::parse("data:image/png;base64,iVBORw0KGgo=",
+                                      base).unwrap(),
+                   CoverSource::Data(Cover {
+                       content_type: "image/png".to_string(),
+                       data: b"\x89PNG\r\n\x1a\n".to_vec(),
+                   }));
+
+        assert_eq!(CoverSource::parse("ftp://covers/1.jpg", base).
+                   unwrap_err().kind(),
+                   ErrorKind::Unsupported);
+
+        assert_eq!(CoverSource::parse("file://remote/1.jpg", base).
+                   unwrap_err().kind(),
+                   ErrorKind::Unsupported);
+
+        assert_eq!(CoverSource::parse("data:image/png;base64,!!", base).
+                   unwrap_err().kind(),
+                   ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_read_local() {
+        let dir = tempfile::tempdir().unwrap();
+
+        fs::write(dir.path().join("1.png"), b"PNG data").unwrap();
+
+        let source = CoverSource::parse("1.png", dir.path()).unwrap();
+
+        assert_eq!(source.read_local().unwrap().unwrap(), Cover {
+            content_type: "image/png".to_string(),
+            data: b"PNG data".to_vec(),
+        });
+
+        assert_eq!(CoverSource::parse("2.png", dir.path()).unwrap().
+                   read_local().unwrap().unwrap_err().kind(),
+                   ErrorKind::NotFound);
+
+        assert!(CoverSource::Http("http://covers/1.jpg".to_string()).
+                read_local().is_none());
+    }
+}
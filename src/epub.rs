@@ -0,0 +1,356 @@
+//! EPUB files, as a source of books: the metadata are read from
+//! the OPF package document, and the cover embedded as `data:` URI.
+
+use std::fs::{self, File};
+use std::io::{Error, ErrorKind, Read, Result};
+use std::path::{Path, PathBuf};
+
+use xml::reader::{EventReader, XmlEvent};
+
+use zip::ZipArchive;
+
+use crate::codex::Book;
+use crate::cover::normalize::Format;
+use crate::opf;
+
+/// Entry of the container, giving the location of the package document
+const CONTAINER: &str = "META-INF/container.xml";
+
+const OPF_MEDIA_TYPE: &str = "application/oebps-package+xml";
+
+/// Media types of the cover images which can be embedded
+const RASTER_MEDIA_TYPES: [&str; 4] =
+    [ "image/jpeg", "image/png", "image/gif", "image/webp" ];
+
+/// Reads the books from an EPUB file,
+/// or from the EPUB files of a directory (and its sub-directories).
+pub fn read<P: AsRef<Path>>(
+    path: P,
+) -> Result<impl Iterator<Item = Result<Book>>> {
+    let p = path.as_ref();
+
+    let files = if p.is_dir() {
+        epub_files(p)?
+    } else {
+        vec![ p.to_path_buf() ]
+    };
+
+    Ok(files.into_iter().map(read_epub))
+}
+
+/// Returns the EPUB files in the directory, in path order.
+fn epub_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?.
+        map(|e| e.map(|e| e.path())).collect::<Result<Vec<PathBuf>>>()?;
+
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            files.extend(epub_files(&path)?);
+        } else if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("epub")) {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
+/// Reads the book from an EPUB file.
+pub fn read_epub<P: AsRef<Path>>(path: P) -> Result<Book> {
+    let p = path.as_ref();
+
+    File::open(p).and_then(|f| ZipArchive::new(f).map_err(zip_error)).
+        and_then(|mut archive| read_archive(&mut archive)).
+        map_err(|cause| Error::new(cause.kind(), format!(
+            "Fails to read '{}': {}", p.display(), cause)))
+}
+
+fn zip_error(cause: zip::result::ZipError) -> Error {
+    match cause {
+        zip::result::ZipError::Io(e) => e,
+        e => Error::new(ErrorKind::InvalidData, e),
+    }
+}
+
+fn read_archive<R: Read + std::io::Seek>(
+    archive: &mut ZipArchive<R>,
+) -> Result<Book> {
+    let opf_path = archive.by_name(CONTAINER).map_err(zip_error).
+        and_then(rootfile)?;
+
+    let package = archive.by_name(&opf_path).map_err(zip_error).
+        and_then(opf::parse)?;
+
+    let mut book = package.book;
+
+    if let Some(cover) = package.cover {
+        let base = opf_path.rsplit_once('/').map_or("", |(dir, _)| dir);
+        let cover_path = resolve(base, &cover.href);
+
+        match read_cover(archive, &cover_path, &cover.media_type) {
+            Ok(uri) => book.cover = uri,
+
+            Err(cause) => log::warn!(
+                target: "epub", "Ignore cover '{}' of '{}': {}",
+                cover_path, book.title, cause),
+        }
+    }
+
+    Ok(book)
+}
+
+/// Returns the location of the package document in the EPUB,
+/// from the container.
+fn rootfile<R: Read>(container: R) -> Result<String> {
+    for event in EventReader::new(container) {
+        match event.map_err(|cause| Error::new(
+            ErrorKind::InvalidData, format!("Invalid container: {}", cause)))? {
+            XmlEvent::StartElement { name, attributes, .. }
+            if name.local_name == "rootfile" => {
+                let attr = |n: &str| attributes.iter().
+                    find(|a| a.name.local_name == n).map(|a| a.value.as_str());
+
+                if let (Some(path), None | Some(OPF_MEDIA_TYPE)) =
+                    (attr("full-path"), attr("media-type")) {
+                    return Ok(path.to_string());
+                }
+            }
+
+            _ => (),
+        }
+    }
+
+    Err(Error::new(ErrorKind::InvalidData, "Missing package document"))
+}
+
+/// Resolves the (URL encoded) reference against the base directory
+/// of the archive.
+fn resolve(base: &str, href: &str) -> String {
+    let mut parts: Vec<String> = base.split('/').filter(|p| !p.is_empty()).
+        map(|p| p.to_string()).collect();
+
+    for part in percent_decode(href).split('/') {
+        match part {
+            "" | "." => (),
+            ".." => {
+                parts.pop();
+            }
+            p => parts.push(p.to_string()),
+        }
+    }
+
+    parts.join("/")
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = value.get(i + 1..i + 3).
+            and_then(|h| u8::from_str_radix(h, 16).ok());
+
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            }
+
+            (b, _) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Reads the cover image from the archive, as `data:` URI,
+/// with its media type from the manifest (or else detected from its data).
+///
+/// The covers which are not raster images (e.g. SVG) are rejected.
+fn read_cover<R: Read + std::io::Seek>(
+    archive: &mut ZipArchive<R>,
+    path: &str,
+    media_type: &str,
+) -> Result<String> {
+    let mut data = vec![];
+
+    archive.by_name(path).map_err(zip_error)?.read_to_end(&mut data)?;
+
+    let content_type = match media_type.trim().to_ascii_lowercase() {
+        t if t.is_empty() => Format::sniff(&data).
+            map(|f| f.content_type().to_string()).
+            ok_or_else(|| Error::new(
+                ErrorKind::InvalidData, "Unknown image format"))?,
+
+        t => t,
+    };
+
+    if !RASTER_MEDIA_TYPES.contains(&content_type.as_str()) {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            format!("Not a raster image ({})", content_type)));
+    }
+
+    Ok(format!("data:{};base64,{}", content_type, base64::encode(&data)))
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    use zip::write::{FileOptions, ZipWriter};
+
+    use crate::model::Isbn;
+
+    const CONTAINER_XML: &str = r#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>"#;
+
+    const CONTENT_OPF: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="uid">urn:uuid:5f9e1b0e-5c44-4bd1-9d3e-6a3c3c2b1f0a</dc:identifier>
+    <dc:identifier>978-2-84172-048-4</dc:identifier>
+    <dc:title>Accros du roc</dc:title>
+    <dc:creator id="creator01">Terry Pratchett</dc:creator>
+    <meta refines="#creator01" property="role" scheme="marc:relators">aut</meta>
+    <meta refines="#creator01" property="file-as">Pratchett, Terry</meta>
+    <dc:contributor id="contributor01">Patrick Couton</dc:contributor>
+    <dc:publisher>L'Atalante</dc:publisher>
+    <dc:date>2004-01</dc:date>
+    <dc:language>fr</dc:language>
+    <dc:description>Suzanne Sto Hélit.</dc:description>
+    <meta property="belongs-to-collection" id="c01">Les Annales du Disque-monde</meta>
+    <meta refines="#c01" property="group-position">27</meta>
+  </metadata>
+  <manifest>
+    <item id="cover" href="images/couverture%20roc.png" media-type="image/png" properties="cover-image"/>
+    <item id="chapter1" href="text/ch1.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+</package>"##;
+
+    fn write_epub(path: &Path, container: &str) {
+        write_package(path, container, CONTENT_OPF,
+                      "OEBPS/images/couverture roc.png", b"\x89PNG");
+    }
+
+    fn write_package(
+        path: &Path,
+        container: &str,
+        opf: &str,
+        cover_path: &str,
+        cover: &[u8],
+    ) {
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        let options = FileOptions::default();
+
+        zip.start_file("mimetype", options).unwrap();
+        zip.write_all(b"application/epub+zip").unwrap();
+        zip.start_file(CONTAINER, options).unwrap();
+        zip.write_all(container.as_bytes()).unwrap();
+        zip.start_file("OEBPS/content.opf", options).unwrap();
+        zip.write_all(opf.as_bytes()).unwrap();
+        zip.start_file(cover_path, options).unwrap();
+        zip.write_all(cover).unwrap();
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_read() {
+        let dir = tempfile::tempdir().unwrap();
+
+        fs::create_dir(dir.path().join("pratchett")).unwrap();
+        write_epub(&dir.path().join("pratchett/roc.epub"), CONTAINER_XML);
+        fs::write(dir.path().join("notes.txt"), b"").unwrap();
+
+        let books: Vec<Book> = read(dir.path()).unwrap().
+            collect::<Result<Vec<Book>>>().unwrap();
+
+        assert_eq!(books.len(), 1);
+
+        let b = &books[0];
+
+        assert_eq!(b.title, "Accros du roc");
+        assert_eq!(b.authors.len(), 1);
+        assert_eq!(b.authors[0].name, "Terry Pratchett");
+        assert_eq!(b.authors[0].last_name, "Pratchett");
+        assert_eq!(b.publisher, "L'Atalante");
+        assert_eq!(b.pubdate, Some(time::date!(2004-01-01)));
+        assert_eq!(b.language, "fr");
+        assert_eq!(b.summary, "Suzanne Sto Hélit.");
+        assert_eq!(b.isbn.iter().map(Isbn::digits).collect::<Vec<String>>(),
+                   vec![ "9782841720484" ]);
+        assert_eq!(b.series, "Les Annales du Disque-monde");
+        assert_eq!(b.volume, Some(27));
+        assert_eq!(b.cover, "data:image/png;base64,iVBORw==");
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("broken.epub");
+
+        write_epub(&path, "<container><rootfiles/></container>");
+
+        assert_eq!(read_epub(&path).err().map(|e| e.to_string()),
+                   Some(format!("Fails to read '{}': Missing package document",
+                                path.display())));
+
+        fs::write(&path, b"not a zip").unwrap();
+
+        assert_eq!(read_epub(&path).err().map(|e| e.kind()),
+                   Some(ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn test_read_cover_media_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("roc.epub");
+        let item = r#"<item id="cover" href="images/couverture%20roc.png" media-type="image/png" properties="cover-image"/>"#;
+
+        // Media type from the manifest, whatever the file name
+        let opf = CONTENT_OPF.replace(item, r#"<item id="cover" href="images/cover" media-type="image/png" properties="cover-image"/>"#);
+
+        write_package(&path, CONTAINER_XML, &opf, "OEBPS/images/cover", b"\x89PNG");
+
+        assert_eq!(read_epub(&path).unwrap().cover,
+                   "data:image/png;base64,iVBORw==");
+
+        // Not a raster image
+        let opf = CONTENT_OPF.replace(item, r#"<item id="cover" href="images/cover.svg" media-type="image/svg+xml" properties="cover-image"/>"#);
+
+        write_package(&path, CONTAINER_XML, &opf, "OEBPS/images/cover.svg", b"<svg/>");
+
+        assert_eq!(read_epub(&path).unwrap().cover, "");
+
+        // Guide reference, not in the manifest: detected from the data
+        let opf = CONTENT_OPF.replace(item, "").replace(
+            "</package>",
+            r#"<guide><reference type="cover" href="images/cover.jpg"/></guide></package>"#);
+
+        write_package(&path, CONTAINER_XML, &opf, "OEBPS/images/cover.jpg", b"GIF89a");
+
+        assert_eq!(read_epub(&path).unwrap().cover,
+                   "data:image/gif;base64,R0lGODlh");
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve("OEBPS", "images/cover.jpg"), "OEBPS/images/cover.jpg");
+        assert_eq!(resolve("OEBPS/text", "../images/a%20b.jpg"), "OEBPS/images/a b.jpg");
+        assert_eq!(resolve("", "./cover.jpg"), "cover.jpg");
+    }
+}
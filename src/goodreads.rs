@@ -0,0 +1,382 @@
+//! Goodreads library export (CSV), as downloaded from
+//! "My Books > Import and export".
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+use csv::{QuoteStyle, StringRecord, Writer};
+
+use time::Date;
+
+use crate::codex::{Author, Book, author_named, author_sorted, empty_book};
+use crate::model::Isbn;
+use crate::pipeline::{BookSink, Entry};
+
+/// Columns of a Goodreads export, in order
+pub const COLUMNS: [&str; 24] = [
+    "Book Id", "Title", "Author", "Author l-f", "Additional Authors",
+    "ISBN", "ISBN13", "My Rating", "Average Rating", "Publisher", "Binding",
+    "Number of Pages", "Year Published", "Original Publication Year",
+    "Date Read", "Date Added", "Bookshelves", "Bookshelves with positions",
+    "Exclusive Shelf", "My Review", "Spoiler", "Private Notes", "Read Count",
+    "Owned Copies",
+];
+
+const GOODREADS_DATE_FORMAT: &str = "%Y/%m/%d";
+
+/// Scheme of the "Book Id" in the book identifiers
+pub const GOODREADS_ID: &str = "goodreads";
+
+/// Exclusive shelf of the books which have been read
+const SHELF_READ: &str = "read";
+const SHELF_TO_READ: &str = "to-read";
+
+/// Reads the books from a Goodreads export,
+/// the columns being looked up by their name in the header row.
+pub fn read<A: Read>(
+    csv_reader: A,
+) -> Result<impl Iterator<Item = Result<Book>>> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).
+        from_reader(csv_reader);
+
+    let columns: HashMap<String, usize> = reader.headers().
+        map_err(Error::other)?.iter().enumerate().
+        map(|(i, name)| (name.trim().to_string(), i)).collect();
+
+    if !columns.contains_key("Title") {
+        return Err(Error::new(ErrorKind::InvalidData,
+                              "Missing Goodreads column 'Title'"));
+    }
+
+    Ok(reader.into_records().map(move |res| res.map_err(Error::other).
+                                 and_then(|record| read_book(&record, &columns))))
+}
+
+fn read_book(
+    record: &StringRecord,
+    columns: &HashMap<String, usize>,
+) -> Result<Book> {
+    let value = |name: &str| columns.get(name).
+        and_then(|i| record.get(*i)).unwrap_or("").trim();
+
+    let invalid = |name: &str, cause: &dyn std::fmt::Display| Error::new(
+        ErrorKind::InvalidData,
+        format!("Invalid {} '{}' at line {}: {}", name.to_lowercase(),
+                value(name), record.position().map_or(0, |p| p.line()), cause));
+
+    let mut book = empty_book();
+
+    if !value("Book Id").is_empty() {
+        book.identifiers.push(
+            (GOODREADS_ID.to_string(), value("Book Id").to_string()));
+    }
+
+    let (title, series, volume) = read_title(value("Title"));
+
+    book.title = title;
+    book.series = series;
+    book.volume = volume;
+
+    let main_author = match (value("Author l-f"), value("Author")) {
+        (lf, name) if !lf.is_empty() => Some(author_sorted(lf, name)),
+        (_, "") => None,
+        (_, name) => Some(author_named(name)),
+    };
+
+    book.authors = main_author.into_iter().chain(
+        value("Additional Authors").split(',').map(str::trim).
+            filter(|n| !n.is_empty()).map(author_named)).collect();
+
+    for name in [ "ISBN13", "ISBN" ] {
+        if let Some(i) = read_isbn(value(name)) {
+            if !book.isbn.contains(&i) {
+                book.isbn.push(i);
+            }
+        }
+    }
+
+    book.rating = match value("My Rating") {
+        "" | "0" => None,
+        r => Some(r.parse().map_err(|e| invalid("My Rating", &e))?),
+    };
+
+    book.publisher = value("Publisher").to_string();
+    book.format = value("Binding").to_string();
+
+    if !value("Number of Pages").is_empty() {
+        book.pages = value("Number of Pages").parse().
+            map_err(|e| invalid("Number of Pages", &e))?;
+    }
+
+    // Only the year is known: the date is the first day of the year
+    // (written as is by the formats with full dates, e.g. Mylib CSV)
+    book.pubdate = match value("Year Published") {
+        "" => None,
+
+        y => Some(y.parse().map_err(|e| invalid("Year Published", &e)).
+                  and_then(|y| Date::try_from_ymd(y, 1, 1).
+                           map_err(|e| invalid("Year Published", &e)))?),
+    };
+
+    book.read_end = read_date(value("Date Read"));
+
+    let shelf = value("Exclusive Shelf");
+
+    book.read = shelf == SHELF_READ || book.read_end.is_some();
+    book.tags = value("Bookshelves").split(',').map(str::trim).
+        filter(|s| !s.is_empty() && *s != shelf).
+        map(|s| s.to_string()).collect();
+
+    book.notes = value("My Review").to_string();
+
+    Ok(book)
+}
+
+/// Splits the series from a Goodreads title (e.g. "Title (Series, #n)").
+fn read_title(value: &str) -> (String, String, Option<u16>) {
+    let series = value.strip_suffix(')').
+        and_then(|v| v.rsplit_once(" (")).
+        and_then(|(title, s)| s.rsplit_once(", #").
+                 map(|(name, num)| (title, name, num.parse().ok())));
+
+    match series {
+        Some((title, name, volume)) =>
+            (title.to_string(), name.to_string(), volume),
+
+        None => (value.to_string(), "".to_string(), None),
+    }
+}
+
+/// Parses an ISBN, as exported in a spreadsheet formula (e.g. `="2841720489"`).
+fn read_isbn(value: &str) -> Option<Isbn> {
+    let digits = value.strip_prefix("=\"").
+        and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+
+    if digits.is_empty() {
+        return None;
+    }
+
+    Isbn::parse(digits).map_or_else(
+        |cause| {
+            log::warn!(target: "goodreads", "Invalid ISBN '{}': {}", digits, cause);
+
+            None
+        },
+        Some)
+}
+
+fn read_date(value: &str) -> Option<Date> {
+    if value.is_empty() {
+        return None;
+    }
+
+    Date::parse(value, GOODREADS_DATE_FORMAT).map_or_else(
+        |cause| {
+            log::warn!(target: "goodreads", "Invalid date '{}': {}", value, cause);
+
+            None
+        },
+        Some)
+}
+
+// ---
+
+/// Returns a CSV writer as expected by the Goodreads import,
+/// after writing the header row.
+pub fn csv_writer<W: Write>(w: W) -> Result<Writer<W>> {
+    let mut writer = csv::WriterBuilder::new().
+        quote_style(QuoteStyle::Necessary).
+        from_writer(w);
+
+    writer.write_record(COLUMNS).map_err(Error::other)?;
+
+    Ok(writer)
+}
+
+/// Writes the book as Goodreads CSV record (see `COLUMNS`).
+pub fn write<W: Write>(csv_writer: &mut Writer<W>, book: &Book) -> Result<()> {
+    let title = match (book.series.as_str(), book.volume) {
+        ("", _) => book.title.to_string(),
+        (series, Some(v)) => format!("{} ({}, #{})", book.title, series, v),
+        (series, None) => format!("{} ({})", book.title, series),
+    };
+
+    let name = |a: &Author| if a.name.is_empty() {
+        format!("{} {}", a.first_name, a.last_name).trim().to_string()
+    } else {
+        a.name.to_string()
+    };
+
+    let (author, author_lf) = book.authors.first().map_or_else(
+        || ("".to_string(), "".to_string()),
+        |a| (name(a), if a.first_name.is_empty() {
+            a.last_name.to_string()
+        } else {
+            format!("{}, {}", a.last_name, a.first_name)
+        }));
+
+    let additional = book.authors.iter().skip(1).
+        map(name).collect::<Vec<String>>().join(", ");
+
+    let isbn = |i: Option<Isbn>| format!(
+        "=\"{}\"", i.map(|i| i.digits()).unwrap_or_default());
+
+    let isbn10 = book.isbn.iter().find_map(Isbn::to_isbn10);
+    let isbn13 = book.isbn.iter().find_map(Isbn::to_isbn13);
+
+    let (shelf, read_count) = if book.read {
+        (SHELF_READ, "1")
+    } else {
+        (SHELF_TO_READ, "0")
+    };
+
+    let id = book.identifiers.iter().find(|(scheme, _)| scheme == GOODREADS_ID).
+        map(|(_, id)| id.to_string()).unwrap_or_default();
+
+    let values: [String; 24] = [
+        id,
+        title,
+        author,
+        author_lf,
+        additional,
+        isbn(isbn10),
+        isbn(isbn13),
+        book.rating.map_or(0, |r| r.round() as u8).to_string(),
+        "".to_string(),
+        book.publisher.to_string(),
+        book.format.to_string(),
+        if book.pages == 0 { "".to_string() } else { book.pages.to_string() },
+        book.pubdate.map(|d| d.year().to_string()).unwrap_or_default(),
+        "".to_string(),
+        book.read_end.map(|d| d.format(GOODREADS_DATE_FORMAT)).
+            unwrap_or_default(),
+        "".to_string(),
+        book.tags.join(", "),
+        "".to_string(),
+        shelf.to_string(),
+        book.notes.to_string(),
+        "".to_string(),
+        "".to_string(),
+        read_count.to_string(),
+        "0".to_string(),
+    ];
+
+    csv_writer.write_record(&values).map_err(Error::other)
+}
+
+/// Sink writing the books as Goodreads CSV (see `write`)
+pub struct GoodreadsSink<W: Write> {
+    csv_writer: Writer<W>,
+}
+
+impl<W: Write> GoodreadsSink<W> {
+    /// Writes the header row (see `csv_writer`).
+    pub fn new(w: W) -> Result<GoodreadsSink<W>> {
+        csv_writer(w).map(|csv_writer| GoodreadsSink { csv_writer })
+    }
+}
+
+impl<W: Write> BookSink for GoodreadsSink<W> {
+    fn write(&mut self, entry: &Entry) -> Result<Option<Error>> {
+        write(&mut self.csv_writer, &entry.book).map(|_| None)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.csv_writer.flush()
+    }
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = "Book Id,Title,Author,Author l-f,Additional Authors,ISBN,ISBN13,My Rating,Average Rating,Publisher,Binding,Number of Pages,Year Published,Original Publication Year,Date Read,Date Added,Bookshelves,Bookshelves with positions,Exclusive Shelf,My Review,Spoiler,Private Notes,Read Count,Owned Copies
+34532,\"Accros du roc (Les Annales du Disque-monde, #27)\",Terry Pratchett,\"Pratchett, Terry\",,\"=\"\"2841720489\"\"\",\"=\"\"9782841720484\"\"\",4,4.12,L'Atalante,Paperback,382,2004,2000,2019/03/14,2018/12/01,\"fantasy, humour\",\"fantasy (#3), humour (#1)\",read,Une <b>excellente</b> satire.,,,1,0
+1234,Good Omens,Terry Pratchett,\"Pratchett, Terry\",Neil Gaiman,=\"\",=\"\",0,4.25,Gollancz,Hardcover,,,1990,,2020/01/05,to-read,to-read (#12),to-read,,,,0,0
+";
+
+    #[test]
+    fn test_read() {
+        let books: Vec<Book> = read(EXPORT.as_bytes()).unwrap().
+            collect::<Result<Vec<Book>>>().unwrap();
+
+        assert_eq!(books.len(), 2);
+
+        let b = &books[0];
+
+        assert_eq!(b.identifiers,
+                   vec![ (GOODREADS_ID.to_string(), "34532".to_string()) ]);
+        assert_eq!(b.title, "Accros du roc");
+        assert_eq!(b.series, "Les Annales du Disque-monde");
+        assert_eq!(b.volume, Some(27));
+        assert_eq!(b.authors.len(), 1);
+        assert_eq!(b.authors[0].first_name, "Terry");
+        assert_eq!(b.authors[0].last_name, "Pratchett");
+        assert_eq!(b.authors[0].name, "Terry Pratchett");
+        assert_eq!(b.isbn.iter().map(Isbn::digits).collect::<Vec<String>>(),
+                   vec![ "9782841720484", "2841720489" ]);
+        assert_eq!(b.rating, Some(4.0));
+        assert_eq!(b.publisher, "L'Atalante");
+        assert_eq!(b.format, "Paperback");
+        assert_eq!(b.pages, 382);
+        assert_eq!(b.pubdate, Some(time::date!(2004-01-01)));
+        assert_eq!(b.read_end, Some(time::date!(2019-03-14)));
+        assert!(b.read);
+        assert_eq!(b.tags, vec![ "fantasy", "humour" ]);
+        assert_eq!(b.notes, "Une <b>excellente</b> satire.");
+
+        let b = &books[1];
+
+        assert_eq!(b.title, "Good Omens");
+        assert_eq!(b.series, "");
+        assert_eq!(b.authors.iter().map(|a| a.name.as_str()).
+                   collect::<Vec<&str>>(),
+                   vec![ "Terry Pratchett", "Neil Gaiman" ]);
+        assert!(b.isbn.is_empty());
+        assert_eq!(b.rating, None);
+        assert_eq!(b.pages, 0);
+        assert_eq!(b.pubdate, None);
+        assert!(!b.read);
+        assert!(b.tags.is_empty());
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        let error = |csv: &str| match read(csv.as_bytes()) {
+            Ok(mut books) => books.next().unwrap().unwrap_err().to_string(),
+            Err(cause) => cause.to_string(),
+        };
+
+        assert_eq!(error("Book Id,Author\n1,Terry Pratchett\n"),
+                   "Missing Goodreads column 'Title'");
+
+        assert_eq!(error("Title,Number of Pages\nAlly,many\n"),
+                   "Invalid number of pages 'many' at line 2: invalid digit found in string");
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let books: Vec<Book> = read(EXPORT.as_bytes()).unwrap().
+            collect::<Result<Vec<Book>>>().unwrap();
+
+        let mut csv = csv_writer(vec![]).unwrap();
+
+        for b in &books {
+            write(&mut csv, b).unwrap();
+        }
+
+        let out = String::from_utf8(csv.into_inner().unwrap()).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+
+        assert_eq!(lines[0], COLUMNS.join(","));
+        assert_eq!(lines[1], "34532,\"Accros du roc (Les Annales du Disque-monde, #27)\",Terry Pratchett,\"Pratchett, Terry\",,\"=\"\"2841720489\"\"\",\"=\"\"9782841720484\"\"\",4,,L'Atalante,Paperback,382,2004,,2019/03/14,,\"fantasy, humour\",,read,Une <b>excellente</b> satire.,,,1,0");
+        assert_eq!(lines[2], "1234,Good Omens,Terry Pratchett,\"Pratchett, Terry\",Neil Gaiman,\"=\"\"\"\"\",\"=\"\"\"\"\",0,,Gollancz,Hardcover,,,,,,,,to-read,,,,0,0");
+
+        let again: Vec<Book> = read(out.as_bytes()).unwrap().
+            collect::<Result<Vec<Book>>>().unwrap();
+
+        assert_eq!(again, books);
+    }
+}
@@ -0,0 +1,53 @@
+//! Conversion of the book exports from [Codex](https://codex.app)
+//! (XML) to [Mylib](https://www.mylib.fr) (CSV), and back.
+//!
+//! - `codex`: the `Book` model, with the Codex XML parser and writer;
+//! - `model`: the ISBN (validation, conversion, hyphenation);
+//! - `mylib`: the Mylib CSV reader and writer, with configurable dialects;
+//! - `cover`: the cover resolver (HTTP with cache and retries, local files,
+//!   `data:` URIs), normalizing the images.
+//!
+//! ```
+//! use codex_mylib::codex::util::parse;
+//! use codex_mylib::mylib::{self, dialect::Dialect};
+//!
+//! let xml = "<books version=\"2\">
+//!   <book>
+//!     <title>Accros du roc</title>
+//!     <authors><author><name>Terry Pratchett</name></author></authors>
+//!     <identifiers>
+//!       <identifier><type>ISBN_13</type><value>9782266211963</value></identifier>
+//!     </identifiers>
+//!   </book>
+//! </books>";
+//!
+//! let dialect = Dialect::default();
+//! let mut csv = mylib::csv_writer(vec![], &dialect).unwrap();
+//!
+//! for book in parse(xml.as_bytes()) {
+//!     let book = book.unwrap();
+//!
+//!     assert_eq!(book.isbn[0].hyphenated().unwrap(), "978-2-266-21196-3");
+//!
+//!     mylib::write(&mut csv, &mut std::io::sink(), &book, &dialect,
+//!                  mylib::DEFAULT_COVER_DIRECTORY, None).unwrap();
+//! }
+//!
+//! let out = String::from_utf8(csv.into_inner().unwrap()).unwrap();
+//!
+//! assert!(out.starts_with("\"Accros du roc\";\"Terry Pratchett\";"));
+//! ```
+
+pub mod calibre;
+pub mod codex;
+pub mod cover;
+pub mod epub;
+pub mod goodreads;
+pub mod model;
+pub mod mylib;
+pub mod opf;
+pub mod pipeline;
+
+pub use codex::{Author, Book};
+pub use model::Isbn;
+pub use pipeline::{BookSink, BookSource, Pipeline};
@@ -1,16 +1,200 @@
-#[derive(Debug)]
+use std::fmt::{Display, Formatter};
+
+mod range;
+
+#[derive(Debug, Clone)]
 pub enum Isbn {
     Isbn10(String),
     Isbn13(u64),
 }
 
+/// ISBN registration group (language area or country).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegistrationGroup {
+    /// Prefix of the group (e.g. "978-2")
+    pub prefix: String,
+    /// Name of the group (e.g. "French language")
+    pub agency: String,
+}
+
+/// Error raised when an ISBN cannot be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IsbnError {
+    /// Neither 10 nor 13 characters (once hyphens and spaces are stripped)
+    Length(usize),
+    /// Not a digit (nor a final 'X' for an ISBN-10)
+    Character(char),
+    /// ISBN-13 not starting with 978 or 979
+    Prefix(String),
+    /// Check digit not matching
+    Checksum,
+}
+
+const ISBN13_PREFIXES: [&str; 2] = ["978", "979"];
+
+impl Isbn {
+    /// Parses an ISBN-10 or ISBN-13, ignoring hyphens and spaces,
+    /// and verifying its check digit.
+    pub fn parse(value: &str) -> Result<Isbn, IsbnError> {
+        let normalized: String = value.chars().
+            filter(|c| *c != '-' && !c.is_whitespace()).
+            map(|c| c.to_ascii_uppercase()).collect();
+
+        match normalized.len() {
+            10 => {
+                if let Some((_, c)) = normalized.chars().enumerate().find(
+                    |(i, c)| !(c.is_ascii_digit() || (*i == 9 && *c == 'X'))) {
+                    return Err(IsbnError::Character(c));
+                }
+
+                if isbn10_check_digit(&normalized[..9]) ==
+                    normalized.chars().last() {
+                    Ok(Isbn::Isbn10(normalized))
+                } else {
+                    Err(IsbnError::Checksum)
+                }
+            }
+
+            13 => {
+                if let Some(c) = normalized.chars().
+                    find(|c| !c.is_ascii_digit()) {
+                    return Err(IsbnError::Character(c));
+                }
+
+                if !ISBN13_PREFIXES.contains(&&normalized[..3]) {
+                    return Err(IsbnError::Prefix(normalized[..3].to_string()));
+                }
+
+                if isbn13_check_digit(&normalized[..12]) ==
+                    normalized.chars().last() {
+                    normalized.parse().
+                        map(Isbn::Isbn13).
+                        map_err(|_| IsbnError::Checksum)
+                } else {
+                    Err(IsbnError::Checksum)
+                }
+            }
+
+            len => Err(IsbnError::Length(len)),
+        }
+    }
+
+    /// Returns the ISBN-13 form, if this ISBN is well-formed.
+    pub fn to_isbn13(&self) -> Option<Isbn> {
+        match self {
+            Isbn::Isbn13(_) => Some(self.clone()),
+
+            Isbn::Isbn10(value) => {
+                let digits = format!("978{}", value.get(..9)?);
+
+                isbn13_check_digit(&digits).
+                    and_then(|c| format!("{}{}", digits, c).parse().ok()).
+                    map(Isbn::Isbn13)
+            }
+        }
+    }
+
+    /// Returns the ISBN-10 form, if any
+    /// (an ISBN-13 starting with 979 has no ISBN-10 equivalent).
+    pub fn to_isbn10(&self) -> Option<Isbn> {
+        match self {
+            Isbn::Isbn10(_) => Some(self.clone()),
+
+            Isbn::Isbn13(value) => {
+                let digits = value.to_string();
+
+                if !digits.starts_with("978") {
+                    return None;
+                }
+
+                let body = digits.get(3..12)?;
+
+                isbn10_check_digit(body).
+                    map(|c| Isbn::Isbn10(format!("{}{}", body, c)))
+            }
+        }
+    }
+
+    /// Returns the canonical hyphenated form (e.g. "978-2-266-21196-3"),
+    /// if the ISBN is covered by the range table.
+    pub fn hyphenated(&self) -> Option<String> {
+        let parts = self.parts()?;
+        let digits = self.digits();
+        let check = digits.chars().last()?;
+
+        match self {
+            Isbn::Isbn10(_) => Some(format!(
+                "{}-{}-{}-{}",
+                parts.group, parts.registrant, parts.publication, check)),
+
+            Isbn::Isbn13(_) => Some(format!(
+                "{}-{}-{}-{}-{}",
+                parts.prefix, parts.group, parts.registrant,
+                parts.publication, check)),
+        }
+    }
+
+    /// Returns the registration group (language area or country).
+    pub fn registration_group(&self) -> Option<RegistrationGroup> {
+        self.parts().map(|parts| RegistrationGroup {
+            prefix: format!("{}-{}", parts.prefix, parts.group),
+            agency: parts.agency,
+        })
+    }
+
+    /// Returns the registrant (publisher) prefix, e.g. "978-2-266".
+    pub fn registrant(&self) -> Option<String> {
+        self.parts().map(|parts| format!(
+            "{}-{}-{}", parts.prefix, parts.group, parts.registrant))
+    }
+
+    fn parts(&self) -> Option<range::Parts> {
+        self.to_isbn13().and_then(|i| range::table().parts(&i.digits()))
+    }
+
+    /// Returns the ISBN digits (without the type prefix of `to_string`).
+    pub fn digits(&self) -> String {
+        match self {
+            Isbn::Isbn10(value) => value.to_string(),
+            Isbn::Isbn13(value) => value.to_string(),
+        }
+    }
+}
+
+/// Returns the ISBN-10 check digit for the given 9 digits (mod 11).
+fn isbn10_check_digit(digits: &str) -> Option<char> {
+    let mut sum: u32 = 0;
+
+    for (i, c) in digits.chars().enumerate() {
+        sum += (10 - i as u32) * c.to_digit(10)?;
+    }
+
+    match (11 - sum % 11) % 11 {
+        10 => Some('X'),
+        d => std::char::from_digit(d, 10),
+    }
+}
+
+/// Returns the ISBN-13 check digit for the given 12 digits (mod 10).
+fn isbn13_check_digit(digits: &str) -> Option<char> {
+    let mut sum: u32 = 0;
+
+    for (i, c) in digits.chars().enumerate() {
+        let weight = if i % 2 == 0 { 1 } else { 3 };
+
+        sum += weight * c.to_digit(10)?;
+    }
+
+    std::char::from_digit((10 - sum % 10) % 10, 10)
+}
+
 // ---
 
-impl ToString for Isbn {
-    fn to_string(&self) -> String {
+impl Display for Isbn {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Isbn::Isbn10(value) => format!("ISBN10:{}", value),
-            Isbn::Isbn13(value) => format!("ISBN13:{}", value),
+            Isbn::Isbn10(value) => write!(formatter, "ISBN10:{}", value),
+            Isbn::Isbn13(value) => write!(formatter, "ISBN13:{}", value),
         }
     }
 }
@@ -30,3 +214,134 @@ impl PartialEq for Isbn {
         }
     }
 }
+
+impl Display for IsbnError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IsbnError::Length(len) =>
+                write!(formatter, "Invalid ISBN length: {}", len),
+
+            IsbnError::Character(c) =>
+                write!(formatter, "Invalid ISBN character: '{}'", c),
+
+            IsbnError::Prefix(prefix) =>
+                write!(formatter, "Invalid ISBN13 prefix: {}", prefix),
+
+            IsbnError::Checksum =>
+                write!(formatter, "Invalid ISBN check digit"),
+        }
+    }
+}
+
+impl std::error::Error for IsbnError {}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_isbn10() {
+        assert_eq!(Isbn::parse("226621196X"),
+                   Ok(Isbn::Isbn10("226621196X".to_string())));
+
+        assert_eq!(Isbn::parse("2-266-21196-x"),
+                   Ok(Isbn::Isbn10("226621196X".to_string())));
+
+        assert_eq!(Isbn::parse("0 306 40615 2"),
+                   Ok(Isbn::Isbn10("0306406152".to_string())));
+
+        assert_eq!(Isbn::parse("2266211969"), Err(IsbnError::Checksum));
+        assert_eq!(Isbn::parse("22662X1969"), Err(IsbnError::Character('X')));
+    }
+
+    #[test]
+    fn test_parse_isbn13() {
+        assert_eq!(Isbn::parse("9782266211963"),
+                   Ok(Isbn::Isbn13(9782266211963)));
+
+        assert_eq!(Isbn::parse("978-2-266-21196-3"),
+                   Ok(Isbn::Isbn13(9782266211963)));
+
+        assert_eq!(Isbn::parse("979-10-90636-07-1"),
+                   Ok(Isbn::Isbn13(9791090636071)));
+
+        assert_eq!(Isbn::parse("9782266211964"), Err(IsbnError::Checksum));
+
+        assert_eq!(Isbn::parse("9772266211963"),
+                   Err(IsbnError::Prefix("977".to_string())));
+
+        assert_eq!(Isbn::parse("97822662119a3"),
+                   Err(IsbnError::Character('A')));
+
+        assert_eq!(Isbn::parse("978226621196"), Err(IsbnError::Length(12)));
+        assert_eq!(Isbn::parse(""), Err(IsbnError::Length(0)));
+    }
+
+    #[test]
+    fn test_to_isbn13() {
+        assert_eq!(Isbn::Isbn10("226621196X".to_string()).to_isbn13(),
+                   Some(Isbn::Isbn13(9782266211963)));
+
+        assert_eq!(Isbn::Isbn10("0306406152".to_string()).to_isbn13(),
+                   Some(Isbn::Isbn13(9780306406157)));
+
+        assert_eq!(Isbn::Isbn13(9782266211963).to_isbn13(),
+                   Some(Isbn::Isbn13(9782266211963)));
+
+        assert_eq!(Isbn::Isbn10("22".to_string()).to_isbn13(), None);
+    }
+
+    #[test]
+    fn test_to_isbn10() {
+        assert_eq!(Isbn::Isbn13(9782266211963).to_isbn10(),
+                   Some(Isbn::Isbn10("226621196X".to_string())));
+
+        assert_eq!(Isbn::Isbn13(9780306406157).to_isbn10(),
+                   Some(Isbn::Isbn10("0306406152".to_string())));
+
+        assert_eq!(Isbn::Isbn13(9791090636071).to_isbn10(), None);
+    }
+
+    #[test]
+    fn test_hyphenated() {
+        assert_eq!(Isbn::Isbn13(9782266211963).hyphenated(),
+                   Some("978-2-266-21196-3".to_string()));
+
+        assert_eq!(Isbn::Isbn10("226621196X".to_string()).hyphenated(),
+                   Some("2-266-21196-X".to_string()));
+
+        assert_eq!(Isbn::Isbn13(9780306406157).hyphenated(),
+                   Some("978-0-306-40615-7".to_string()));
+
+        assert_eq!(Isbn::Isbn13(9791090636071).hyphenated(),
+                   Some("979-10-90636-07-1".to_string()));
+
+        assert_eq!(Isbn::Isbn13(9790230671187).hyphenated(), None);
+    }
+
+    #[test]
+    fn test_registration_group() {
+        assert_eq!(Isbn::Isbn13(9782266211963).registration_group(),
+                   Some(RegistrationGroup {
+                       prefix: "978-2".to_string(),
+                       agency: "French language".to_string(),
+                   }));
+
+        assert_eq!(Isbn::Isbn10("0306406152".to_string()).registration_group(),
+                   Some(RegistrationGroup {
+                       prefix: "978-0".to_string(),
+                       agency: "English language".to_string(),
+                   }));
+    }
+
+    #[test]
+    fn test_registrant() {
+        assert_eq!(Isbn::Isbn13(9782266211963).registrant(),
+                   Some("978-2-266".to_string()));
+
+        assert_eq!(Isbn::Isbn10("226621196X".to_string()).registrant(),
+                   Some("978-2-266".to_string()));
+    }
+}
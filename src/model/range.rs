@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use xml::reader::{EventReader, XmlEvent};
+
+/// Copy of the International ISBN Agency RangeMessage.xml
+const RANGE_MESSAGE: &str = include_str!("../../resources/RangeMessage.xml");
+
+/// Number of digits the ranges are expressed on
+const RANGE_DIGITS: usize = 7;
+
+struct Rule {
+    from: u32,
+    to: u32,
+    length: usize,
+}
+
+/// EAN.UCC prefix (e.g. "978") or registration group (e.g. "978-2").
+struct Group {
+    agency: String,
+    rules: Vec<Rule>,
+}
+
+pub struct RangeTable {
+    groups: HashMap<String, Group>,
+}
+
+/// Hyphenation parts of an ISBN-13.
+pub struct Parts {
+    /// EAN.UCC prefix (e.g. "978")
+    pub prefix: String,
+    /// Registration group identifier (e.g. "2")
+    pub group: String,
+    /// Agency of the registration group (e.g. "French language")
+    pub agency: String,
+    pub registrant: String,
+    pub publication: String,
+}
+
+/// Returns the embedded range table (parsed once).
+pub fn table() -> &'static RangeTable {
+    static TABLE: OnceLock<RangeTable> = OnceLock::new();
+
+    TABLE.get_or_init(|| RangeTable::parse(RANGE_MESSAGE))
+}
+
+impl RangeTable {
+    fn parse(message: &str) -> RangeTable {
+        let mut groups = HashMap::new();
+
+        let mut path: Vec<String> = vec![];
+        let mut prefix = "".to_string();
+        let mut agency = "".to_string();
+        let mut rules: Vec<Rule> = vec![];
+        let mut range: Option<(u32, u32)> = None;
+
+        for e in EventReader::new(message.as_bytes()) {
+            match e {
+                Ok(XmlEvent::StartElement { name, .. }) => {
+                    path.push(name.local_name);
+                }
+
+                Ok(XmlEvent::EndElement { name }) => {
+                    path.pop();
+
+                    if name.local_name == "EAN.UCC" ||
+                        name.local_name == "Group" {
+                        groups.insert(
+                            std::mem::take(&mut prefix),
+                            Group {
+                                agency: std::mem::take(&mut agency),
+                                rules: std::mem::take(&mut rules),
+                            });
+                    }
+                }
+
+                Ok(XmlEvent::Characters(value)) => {
+                    match path.last().map(|n| n.as_str()) {
+                        Some("Prefix") => prefix = value,
+                        Some("Agency") => agency = value,
+
+                        Some("Range") => {
+                            range = value.split_once('-').and_then(
+                                |(from, to)| from.parse().ok().
+                                    zip(to.parse().ok()));
+                        }
+
+                        Some("Length") => {
+                            match (range.take(), value.parse()) {
+                                (Some((from, to)), Ok(length)) =>
+                                    rules.push(Rule { from, to, length }),
+
+                                _ => log::warn!(
+                                    "Invalid ISBN range rule in {}", prefix),
+                            }
+                        }
+
+                        _ => (),
+                    }
+                }
+
+                Ok(_) => (),
+
+                Err(cause) => {
+                    log::warn!("Invalid ISBN range message: {}", cause);
+
+                    break;
+                }
+            }
+        }
+
+        RangeTable { groups }
+    }
+
+    /// Splits the 13 digits of an ISBN into its hyphenation parts,
+    /// if covered by the range table.
+    pub fn parts(&self, digits: &str) -> Option<Parts> {
+        let prefix = digits.get(..3)?;
+        let body = digits.get(3..12)?;
+
+        let group_len = self.groups.get(prefix)?.length(body)?;
+        let group = &body[..group_len];
+
+        let registration = self.groups.get(&format!("{}-{}", prefix, group))?;
+        let registrant_len = registration.length(&body[group_len..])?;
+
+        if group_len + registrant_len >= body.len() {
+            return None;
+        }
+
+        Some(Parts {
+            prefix: prefix.to_string(),
+            group: group.to_string(),
+            agency: registration.agency.to_string(),
+            registrant: body[group_len..group_len + registrant_len].to_string(),
+            publication: body[group_len + registrant_len..].to_string(),
+        })
+    }
+}
+
+impl Group {
+    /// Returns the length of the next part of the given digits,
+    /// according the rule matching their first 7 digits.
+    fn length(&self, digits: &str) -> Option<usize> {
+        let padded = format!("{:0<width$}", digits, width = RANGE_DIGITS);
+        let value: u32 = padded.get(..RANGE_DIGITS)?.parse().ok()?;
+
+        self.rules.iter().
+            find(|r| r.from <= value && value <= r.to).
+            map(|r| r.length).
+            filter(|l| *l > 0 && *l <= digits.len())
+    }
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parts() {
+        let parts = table().parts("9782266211963").unwrap();
+
+        assert_eq!(parts.prefix, "978");
+        assert_eq!(parts.group, "2");
+        assert_eq!(parts.agency, "French language");
+        assert_eq!(parts.registrant, "266");
+        assert_eq!(parts.publication, "21196");
+
+        let parts = table().parts("9791090636071").unwrap();
+
+        assert_eq!(parts.group, "10");
+        assert_eq!(parts.agency, "France");
+        assert_eq!(parts.registrant, "90636");
+        assert_eq!(parts.publication, "07");
+
+        // Range not allocated to a registration group (ISMN)
+        assert!(table().parts("9790230671187").is_none());
+    }
+}
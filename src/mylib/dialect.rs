@@ -0,0 +1,374 @@
+use std::borrow::Cow;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::path::Path;
+
+use csv::{QuoteStyle, Reader, Terminator, Writer};
+
+use encoding_rs::Encoding;
+
+use toml::Value;
+
+use super::template::Template;
+
+/// Built-in profile, the historical Mylib layout
+const DEFAULT_PROFILE: &str = include_str!("../../resources/mylib-default.toml");
+
+/// Book property mapped to a Mylib column
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Title,
+    Subtitle,
+    Authors,
+    Series,
+    Categories,
+    PublishDate,
+    Publisher,
+    PageCount,
+    /// ISBN-13 (converted from the ISBN-10 if needed)
+    Isbn,
+    Isbn10,
+    Language,
+    Format,
+    Rating,
+    Read,
+    ReadingPeriod,
+    LoanedTo,
+    Notes,
+    Description,
+    Tags,
+    Cover,
+    /// Column left empty
+    Empty,
+}
+
+const FIELDS: [(&str, Field); 20] = [
+    ("title", Field::Title),
+    ("subtitle", Field::Subtitle),
+    ("authors", Field::Authors),
+    ("series", Field::Series),
+    ("categories", Field::Categories),
+    ("publishDate", Field::PublishDate),
+    ("publisher", Field::Publisher),
+    ("pageCount", Field::PageCount),
+    ("isbn", Field::Isbn),
+    ("isbn10", Field::Isbn10),
+    ("language", Field::Language),
+    ("format", Field::Format),
+    ("rating", Field::Rating),
+    ("read", Field::Read),
+    ("readingPeriod", Field::ReadingPeriod),
+    ("loanedTo", Field::LoanedTo),
+    ("notes", Field::Notes),
+    ("description", Field::Description),
+    ("tags", Field::Tags),
+    ("cover", Field::Cover),
+];
+
+impl Field {
+    fn parse(name: &str) -> Option<Field> {
+        FIELDS.iter().find(|(n, _)| *n == name).map(|(_, f)| *f)
+    }
+
+    fn name(self) -> &'static str {
+        FIELDS.iter().find(|(_, f)| *f == self).map_or("", |(n, _)| n)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    /// Name in the header row
+    pub name: String,
+    pub field: Field,
+    /// Template of the value, instead of the field
+    /// (the column being ignored when reading)
+    pub template: Option<Template>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Quoting {
+    Always,
+    Necessary,
+    NonNumeric,
+    Never,
+}
+
+impl Quoting {
+    fn style(self) -> QuoteStyle {
+        match self {
+            Quoting::Always => QuoteStyle::Always,
+            Quoting::Necessary => QuoteStyle::Necessary,
+            Quoting::NonNumeric => QuoteStyle::NonNumeric,
+            Quoting::Never => QuoteStyle::Never,
+        }
+    }
+}
+
+/// Layout of the Mylib CSV files (columns, delimiter, quoting, encoding, ...),
+/// as loaded from a TOML profile (see `resources/mylib-default.toml`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dialect {
+    pub delimiter: u8,
+    pub quoting: Quoting,
+    pub encoding: &'static Encoding,
+    /// Whether the first row gives the column names
+    pub header: bool,
+    /// Whether the rows end with CRLF (rather than LF)
+    pub crlf: bool,
+    pub columns: Vec<Column>,
+}
+
+impl Default for Dialect {
+    fn default() -> Dialect {
+        Dialect::parse(DEFAULT_PROFILE).expect("Invalid default Mylib dialect")
+    }
+}
+
+fn invalid<E: std::fmt::Display>(cause: E) -> Error {
+    Error::new(ErrorKind::InvalidData,
+               format!("Invalid Mylib dialect: {}", cause))
+}
+
+impl Dialect {
+    /// Loads the TOML profile from the given file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Dialect> {
+        let p = path.as_ref();
+
+        std::fs::read_to_string(p).and_then(|s| Dialect::parse(&s)).map_err(
+            |cause| Error::new(cause.kind(), format!(
+                "Fails to load '{}': {}", p.display(), cause)))
+    }
+
+    /// Parses a TOML profile.
+    pub fn parse(profile: &str) -> Result<Dialect> {
+        let value: Value = profile.parse().map_err(invalid)?;
+        let table = value.as_table().ok_or_else(|| invalid("not a table"))?;
+
+        if let Some(key) = table.keys().find(|k| !matches!(
+            k.as_str(), "delimiter" | "quoting" | "encoding" | "header" |
+            "line-ending" | "columns")) {
+            return Err(invalid(format!("unknown setting '{}'", key)));
+        }
+
+        let string = |key: &str, default: &'static str| match table.get(key) {
+            None => Ok(default.to_string()),
+            Some(Value::String(s)) => Ok(s.to_string()),
+            Some(_) => Err(invalid(format!("'{}' must be a string", key))),
+        };
+
+        let delimiter = match string("delimiter", ";")?.as_bytes() {
+            [ d ] if d.is_ascii() => *d,
+            _ => return Err(invalid("'delimiter' must be a single ASCII character")),
+        };
+
+        let quoting = match string("quoting", "non-numeric")?.as_str() {
+            "always" => Quoting::Always,
+            "necessary" => Quoting::Necessary,
+            "non-numeric" => Quoting::NonNumeric,
+            "never" => Quoting::Never,
+            q => return Err(invalid(format!("unknown quoting '{}'", q))),
+        };
+
+        let label = string("encoding", "utf-8")?;
+        let encoding = Encoding::for_label(label.as_bytes()).ok_or_else(
+            || invalid(format!("unknown encoding '{}'", label)))?;
+
+        let header = match table.get("header") {
+            None => false,
+            Some(Value::Boolean(b)) => *b,
+            Some(_) => return Err(invalid("'header' must be a boolean")),
+        };
+
+        let crlf = match string("line-ending", "lf")?.as_str() {
+            "lf" => false,
+            "crlf" => true,
+            e => return Err(invalid(format!("unknown line ending '{}'", e))),
+        };
+
+        let columns = match table.get("columns") {
+            Some(Value::Array(cs)) if !cs.is_empty() =>
+                cs.iter().map(column).collect::<Result<Vec<Column>>>()?,
+
+            _ => return Err(invalid("'columns' must be a non empty array")),
+        };
+
+        Ok(Dialect { delimiter, quoting, encoding, header, crlf, columns })
+    }
+
+    /// Returns a CSV writer with this dialect (not writing the header).
+    pub fn csv_writer<W: Write>(&self, w: W) -> Writer<W> {
+        csv::WriterBuilder::new().
+            delimiter(self.delimiter).
+            quote_style(self.quoting.style()).
+            terminator(if self.crlf {
+                Terminator::CRLF
+            } else {
+                Terminator::Any(b'\n')
+            }).
+            from_writer(w)
+    }
+
+    /// Returns a CSV reader with this dialect (skipping the header).
+    pub fn csv_reader<R: Read>(&self, r: R) -> Reader<R> {
+        csv::ReaderBuilder::new().
+            delimiter(self.delimiter).
+            has_headers(self.header).
+            from_reader(r)
+    }
+
+    /// Encodes a value with the dialect encoding,
+    /// the characters which cannot be represented being replaced.
+    pub fn encode<'a>(&self, value: &'a str) -> Cow<'a, [u8]> {
+        let (bytes, _, replaced) = self.encoding.encode(value);
+
+        if replaced {
+            log::warn!(target: "mylib", "Cannot fully encode '{}' as {}",
+                       value, self.encoding.name());
+        }
+
+        bytes
+    }
+
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> Cow<'a, str> {
+        self.encoding.decode_without_bom_handling(bytes).0
+    }
+}
+
+fn column(value: &Value) -> Result<Column> {
+    let table = value.as_table().ok_or_else(
+        || invalid("column must be a table"))?;
+
+    let field = match table.get("field") {
+        None => Field::Empty,
+
+        Some(Value::String(f)) => Field::parse(f).ok_or_else(
+            || invalid(format!("unknown field '{}'", f)))?,
+
+        Some(_) => return Err(invalid("column 'field' must be a string")),
+    };
+
+    let template = match (table.get("template"), field) {
+        (None, _) => None,
+
+        (Some(Value::String(t)), Field::Empty) =>
+            Some(Template::parse(t).map_err(invalid)?),
+
+        (Some(Value::String(_)), _) =>
+            return Err(invalid("column has both 'field' and 'template'")),
+
+        (Some(_), _) => return Err(invalid("column 'template' must be a string")),
+    };
+
+    let name = match (table.get("name"), &template) {
+        (Some(Value::String(n)), _) => n.to_string(),
+        (Some(_), _) => return Err(invalid("column 'name' must be a string")),
+        (None, Some(t)) => t.source().to_string(),
+        (None, None) => field.name().to_string(),
+    };
+
+    Ok(Column { name, field, template })
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default() {
+        let dialect = Dialect::default();
+
+        assert_eq!(dialect.delimiter, b';');
+        assert_eq!(dialect.quoting, Quoting::NonNumeric);
+        assert_eq!(dialect.encoding, encoding_rs::UTF_8);
+        assert!(!dialect.header);
+        assert!(!dialect.crlf);
+
+        let fields: Vec<Field> =
+            dialect.columns.iter().map(|c| c.field).collect();
+
+        assert_eq!(fields, vec![
+            Field::Title, Field::Authors, Field::Series, Field::Categories,
+            Field::PublishDate, Field::Publisher, Field::PageCount,
+            Field::Isbn, Field::Read, Field::ReadingPeriod, Field::Notes,
+            Field::Description, Field::Cover,
+        ]);
+
+        assert_eq!(dialect.columns[0].name, "title");
+    }
+
+    #[test]
+    fn test_parse() {
+        let dialect = Dialect::parse(r#"
+delimiter = ","
+quoting = "necessary"
+encoding = "Windows-1252"
+header = true
+line-ending = "crlf"
+
+[[columns]]
+name = "Titre"
+field = "title"
+
+[[columns]]
+name = "Emplacement"
+
+[[columns]]
+field = "isbn10"
+"#).unwrap();
+
+        assert_eq!(dialect.delimiter, b',');
+        assert_eq!(dialect.encoding, encoding_rs::WINDOWS_1252);
+        assert!(dialect.header && dialect.crlf);
+
+        assert_eq!(dialect.columns, vec![
+            Column {
+                name: "Titre".to_string(),
+                field: Field::Title,
+                template: None,
+            },
+            Column {
+                name: "Emplacement".to_string(),
+                field: Field::Empty,
+                template: None,
+            },
+            Column {
+                name: "isbn10".to_string(),
+                field: Field::Isbn10,
+                template: None,
+            },
+        ]);
+
+        assert_eq!(dialect.encode("Échopraxie").as_ref(), b"\xC9chopraxie");
+        assert_eq!(dialect.decode(b"\xC9l\xE9vation"), "Élévation");
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let error = |profile: &str| Dialect::parse(profile).
+            unwrap_err().to_string();
+
+        let columns = "\n[[columns]]\nfield = \"title\"\n";
+
+        assert_eq!(error(&format!("delimiter = \";;\"{}", columns)),
+                   "Invalid Mylib dialect: 'delimiter' must be a single ASCII character");
+
+        assert_eq!(error(&format!("encoding = \"klingon\"{}", columns)),
+                   "Invalid Mylib dialect: unknown encoding 'klingon'");
+
+        assert_eq!(error(&format!("separator = \",\"{}", columns)),
+                   "Invalid Mylib dialect: unknown setting 'separator'");
+
+        assert_eq!(error("header = true"),
+                   "Invalid Mylib dialect: 'columns' must be a non empty array");
+
+        assert_eq!(error("[[columns]]\nfield = \"isbn12\""),
+                   "Invalid Mylib dialect: unknown field 'isbn12'");
+
+        assert_eq!(error("[[columns]]\nfield = \"title\"\ntemplate = \"{title}\""),
+                   "Invalid Mylib dialect: column has both 'field' and 'template'");
+
+        assert!(error("[[columns]]\ntemplate = \"{title|%Y}\"").
+                starts_with("Invalid Mylib dialect: Invalid template '{title|%Y}'"));
+    }
+}
@@ -0,0 +1,910 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Error, ErrorKind, Read, Result, Write};
+
+use csv::{ByteRecord, Writer};
+
+use crate::codex::{Author, Book, empty_author, empty_book};
+use crate::cover::{Cover, DEFAULT_CONTENT_TYPE, placeholder::Placeholder};
+use crate::model::Isbn;
+use crate::pipeline::{BookSink, Entry};
+
+pub mod dialect;
+pub mod template;
+
+use dialect::{Dialect, Field};
+
+pub const DEFAULT_COVER_DIRECTORY: &str = "/MyLibrary/Images/Books";
+
+/// Boolean tokens as expected by Mylib import (e.g. for "lu")
+const MYLIB_TRUE: &str = "1";
+const MYLIB_FALSE: &str = "0";
+
+const MYLIB_DATE_FORMAT: &str = "%d/%m/%Y";
+
+/// Returns the URL and ISBN to fetch the book cover, if any
+/// (see `cover::CoverFetcher::fetch`).
+pub fn cover_request(book: &Book) -> Option<(String, String)> {
+    if book.cover.is_empty() {
+        None
+    } else {
+        Some((book.cover.to_string(), isbn13(book)))
+    }
+}
+
+/// Returns a CSV writer with the Mylib dialect (see `write`),
+/// after writing the header row if the dialect has one.
+pub fn csv_writer<W: Write>(w: W, dialect: &Dialect) -> Result<Writer<W>> {
+    let mut writer = dialect.csv_writer(w);
+
+    if dialect.header {
+        writer.write_record(
+            dialect.columns.iter().map(|c| dialect.encode(&c.name))).
+            map_err(Error::other)?;
+    }
+
+    Ok(writer)
+}
+
+/// Writes the book as Mylib CSV record, with the columns of the dialect,
+/// and its cover (if fetched) to the images.
+///
+/// Returns the cause of the cover failure, if any
+/// (the cover column is then left empty).
+pub fn write<A: Write, B: Write>(
+    csv_writer: &mut Writer<A>,
+    img_writer: &mut B,
+    book: &Book,
+    dialect: &Dialect,
+    cover_dir: &str,
+    fetched_cover: Option<Result<Cover>>,
+) -> Result<Option<Error>> {
+    let authors: Vec<String> =
+        book.authors.iter().map(|a| a.name.to_string()).collect();
+
+    let isbn = isbn13(book);
+
+    let cover: Result<Option<String>> = match fetched_cover {
+        Some(fetched) => {
+            let url: &String = &book.cover;
+
+            log::info!(target: "mylib", "Cover URL: {}", url);
+
+            book_hashcode(&book.title, &authors).
+                and_then(|h| fetched.
+                         and_then(|c| write_cover(c, h, img_writer)).
+                         map_err(|cause| {
+                             log::warn!(target: "mylib",
+                                        "Fails to resolve cover '{}': {}",
+                                        url, cause);
+
+                             cause
+                         }).
+                         map(|c| {
+                             let file_ext = match c.as_str() {
+                                 "image/png" => "png",
+                                 _ => "jpg"
+                             };
+
+                             let id: String = {
+                                 let i = &isbn;
+
+                                 if i.is_empty() {
+                                     h.to_string()
+                                 } else {
+                                     i.to_string()
+                                 }
+                             };
+
+                             Some(format!("{}/{}.{}", cover_dir, id, file_ext))
+                         }))
+        }
+
+        None => Ok(None),
+    };
+
+    let (cover_path, cover_failure) = match cover {
+        Ok(path) => (path.unwrap_or_default(), None),
+        Err(cause) => ("".to_string(), Some(cause)),
+    };
+
+    let values: Vec<String> = dialect.columns.iter().map(|c| match (&c.template, c.field) {
+        (Some(t), _) => t.render(&template::Context { book, cover: &cover_path }),
+        (None, Field::Isbn) => isbn.to_string(),
+        (None, Field::Cover) => cover_path.to_string(),
+        (None, field) => field_value(book, field),
+    }).collect();
+
+    match csv_writer.write_record(values.iter().map(|v| dialect.encode(v))) {
+        Ok(_) => csv_writer.flush().map(|_| cover_failure),
+        Err(cause) => Err(Error::other(cause)),
+    }
+}
+
+/// Returns the Mylib value of the book field.
+fn field_value(book: &Book, field: Field) -> String {
+    let date = |d: Option<time::Date>| d.
+        map(|d| d.format(MYLIB_DATE_FORMAT)).unwrap_or_default();
+
+    match field {
+        Field::Title => book.title.to_string(),
+        Field::Subtitle => book.subtitle.to_string(),
+        Field::Authors => book.authors.iter().
+            map(|a| a.name.to_string()).collect::<Vec<String>>().join(", "),
+        Field::Series => serie(book),
+        Field::Categories => book.kind.join(", "),
+        Field::PublishDate => date(book.pubdate),
+        Field::Publisher => book.publisher.to_string(),
+        Field::PageCount => book.pages.to_string(),
+        Field::Isbn => isbn13(book),
+        Field::Isbn10 => book.isbn.iter().find_map(Isbn::to_isbn10).
+            map(|i| i.digits()).unwrap_or_default(),
+        Field::Language => book.language.to_string(),
+        Field::Format => book.format.to_string(),
+        Field::Rating => book.rating.map(|r| r.to_string()).unwrap_or_default(),
+        Field::Read =>
+            (if book.read { MYLIB_TRUE } else { MYLIB_FALSE }).to_string(),
+        Field::ReadingPeriod => period(book),
+        Field::LoanedTo => book.loaned_to.to_string(),
+        Field::Notes => book.notes.to_string(),
+        Field::Description => book.summary.to_string(),
+        Field::Tags => book.tags.join(", "),
+        Field::Cover => book.cover.to_string(),
+        Field::Empty => "".to_string(),
+    }
+}
+
+/// Returns the ISBN13 of the book (converted from the ISBN10 if needed).
+fn isbn13(book: &Book) -> String {
+    book.isbn.iter().find(|i| matches!(i, Isbn::Isbn13(_))).
+        or(book.isbn.first()).map_or_else(
+            || "".to_string(),
+            |i| i.to_isbn13().unwrap_or_else(|| i.clone()).digits())
+}
+
+/// Returns the Mylib serie for the book (e.g. "Name #n").
+fn serie(book: &Book) -> String {
+    if book.series.is_empty() {
+        "".to_string()
+    } else {
+        book.volume.map_or_else(
+            || book.series.to_string(),
+            |v| format!("{} #{}", book.series, v))
+    }
+}
+
+/// Returns the Mylib reading period (e.g. "dd/mm/yyyy - dd/mm/yyyy").
+fn period(book: &Book) -> String {
+    match (book.read_start, book.read_end) {
+        (None, None) => "".to_string(),
+
+        (start, end) => format!(
+            "{} - {}",
+            start.map(|d| d.format(MYLIB_DATE_FORMAT)).unwrap_or_default(),
+            end.map(|d| d.format(MYLIB_DATE_FORMAT)).unwrap_or_default()),
+    }
+}
+
+/// Sink writing the books as Mylib CSV (see `write`)
+pub struct MylibSink<A: Write, B: Write> {
+    csv_writer: Writer<A>,
+    img_writer: B,
+    dialect: Dialect,
+    cover_dir: String,
+}
+
+impl<A: Write, B: Write> MylibSink<A, B> {
+    /// Writes the header row if the dialect has one (see `csv_writer`).
+    pub fn new(
+        csv: A,
+        images: B,
+        dialect: Dialect,
+        cover_dir: &str,
+    ) -> Result<MylibSink<A, B>> {
+        Ok(MylibSink {
+            csv_writer: csv_writer(csv, &dialect)?,
+            img_writer: images,
+            dialect,
+            cover_dir: cover_dir.to_string(),
+        })
+    }
+}
+
+impl<A: Write, B: Write> BookSink for MylibSink<A, B> {
+    fn write(&mut self, entry: &Entry) -> Result<Option<Error>> {
+        let cover = entry.cover.as_ref().map(|res| match res {
+            Ok(c) => Ok(c.clone()),
+
+            // Copy of the cause, still identified as placeholder if so
+            Err(cause) => Err(
+                match cause.get_ref().and_then(|e| e.downcast_ref::<Placeholder>()) {
+                    Some(p) => Placeholder(p.0.to_string()).into(),
+                    None => Error::new(cause.kind(), cause.to_string()),
+                }),
+        });
+
+        write(&mut self.csv_writer, &mut self.img_writer, &entry.book,
+              &self.dialect, &self.cover_dir, cover)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.csv_writer.flush().and_then(|_| self.img_writer.flush())
+    }
+}
+
+/// Reads the books from a Mylib CSV export (as emitted by `write`
+/// with the same dialect), with the covers embedded from the images
+/// (JSON lines) as `data:` URIs.
+///
+/// A cover which is not found in the images is kept only if it's a URL.
+pub fn read<A: Read, B: BufRead>(
+    csv_reader: A,
+    img_reader: B,
+    dialect: &Dialect,
+) -> Result<impl Iterator<Item = Result<Book>>> {
+    let images = read_images(img_reader)?;
+    let dialect = dialect.clone();
+
+    let records = dialect.csv_reader(csv_reader).into_byte_records();
+
+    Ok(records.map(move |res| res.map_err(Error::other).
+                   and_then(|record| read_book(&record, &dialect, &images))))
+}
+
+fn read_images<B: BufRead>(img_reader: B) -> Result<HashMap<i32, String>> {
+    let mut images = HashMap::new();
+
+    for (n, line) in img_reader.lines().enumerate() {
+        let l = line?;
+        let trimmed = l.trim();
+
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        let json_cover = json::parse(trimmed).map_err(|cause| Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid cover image: {}", cause)))?;
+
+        match (json_cover["elementHashcode"].as_i32(),
+               json_cover["base64Image"].as_str()) {
+            (Some(h), Some(b64img)) => {
+                images.insert(h, b64img.to_string());
+            }
+
+            _ => log::warn!(target: "mylib",
+                            "Ignore incomplete cover image at line {}",
+                            n + 1),
+        }
+    }
+
+    Ok(images)
+}
+
+fn read_book(
+    record: &ByteRecord,
+    dialect: &Dialect,
+    images: &HashMap<i32, String>,
+) -> Result<Book> {
+    if record.len() != dialect.columns.len() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Expected {} columns at line {}: {}",
+                    dialect.columns.len(),
+                    record.position().map_or(0, |p| p.line()),
+                    record.len())));
+    }
+
+    let mut book = empty_book();
+    let mut cover_path = String::new();
+
+    for (column, bytes) in dialect.columns.iter().zip(record.iter()) {
+        let value = dialect.decode(bytes);
+
+        if column.field == Field::Cover {
+            cover_path = value.to_string();
+        } else {
+            read_field(&mut book, column.field, &value)?;
+        }
+    }
+
+    let authors: Vec<String> =
+        book.authors.iter().map(|a| a.name.to_string()).collect();
+
+    book.cover = book_hashcode(&book.title, &authors).ok().
+        and_then(|h| images.get(&h)).map_or_else(
+            || {
+                if cover_path.starts_with("http://") ||
+                    cover_path.starts_with("https://") {
+                    cover_path.to_string()
+                } else {
+                    "".to_string()
+                }
+            },
+            |b64img| {
+                let tpe = if cover_path.ends_with(".png") {
+                    "image/png"
+                } else {
+                    DEFAULT_CONTENT_TYPE
+                };
+
+                format!("data:{};base64,{}", tpe, b64img)
+            });
+
+    Ok(book)
+}
+
+/// Sets the book field from its Mylib value; reverse of `field_value`.
+fn read_field(book: &mut Book, field: Field, value: &str) -> Result<()> {
+    match field {
+        Field::Title => book.title = value.to_string(),
+        Field::Subtitle => book.subtitle = value.to_string(),
+        Field::Authors =>
+            book.authors = split_list(value).into_iter().map(author).collect(),
+
+        Field::Series => {
+            let (series, volume) = read_serie(value);
+
+            book.series = series;
+            book.volume = volume;
+        }
+
+        Field::Categories => book.kind = split_list(value),
+        Field::PublishDate => book.pubdate = read_date(value),
+        Field::Publisher => book.publisher = value.to_string(),
+
+        Field::PageCount if !value.is_empty() => {
+            book.pages = value.parse().map_err(|cause| Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid page count '{}': {}", value, cause)))?;
+        }
+
+        // As in Codex exports, provide the ISBN-10 along with the ISBN-13
+        Field::Isbn | Field::Isbn10 => if let Some(i) = read_isbn(value) {
+            let isbn = i.to_isbn13().unwrap_or(i);
+            let isbns = match isbn.to_isbn10() {
+                Some(isbn10) if isbn10 != isbn => vec![isbn, isbn10],
+                _ => vec![isbn],
+            };
+
+            for i in isbns {
+                if !book.isbn.contains(&i) {
+                    book.isbn.push(i);
+                }
+            }
+        },
+
+        Field::Language => book.language = value.to_string(),
+        Field::Format => book.format = value.to_string(),
+
+        Field::Rating if !value.is_empty() => {
+            book.rating = Some(value.parse().map_err(|cause| Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid rating '{}': {}", value, cause)))?);
+        }
+
+        Field::Read => book.read = value == MYLIB_TRUE,
+
+        Field::ReadingPeriod => {
+            let (read_start, read_end) = read_period(value);
+
+            book.read_start = read_start;
+            book.read_end = read_end;
+        }
+
+        Field::LoanedTo => book.loaned_to = value.to_string(),
+        Field::Notes => book.notes = value.to_string(),
+        Field::Description => book.summary = value.to_string(),
+        Field::Tags => book.tags = split_list(value),
+        Field::Cover => book.cover = value.to_string(),
+
+        Field::PageCount | Field::Rating | Field::Empty => (),
+    }
+
+    Ok(())
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value.split(", ").filter(|v| !v.is_empty()).
+        map(|v| v.to_string()).collect()
+}
+
+/// Returns the author for the given full name,
+/// considering the first word as the first name.
+fn author(name: String) -> Author {
+    let mut a = empty_author();
+
+    if let Some((first, last)) = name.split_once(' ') {
+        a.first_name = first.to_string();
+        a.last_name = last.to_string();
+    }
+
+    a.name = name;
+
+    a
+}
+
+/// Parses a Mylib serie (e.g. "Name #n"); reverse of `serie`.
+fn read_serie(value: &str) -> (String, Option<u16>) {
+    match value.rsplit_once(" #") {
+        Some((name, num)) => match num.parse() {
+            Ok(v) => (name.to_string(), Some(v)),
+            Err(_) => (value.to_string(), None),
+        },
+
+        None => (value.to_string(), None),
+    }
+}
+
+/// Parses a Mylib reading period; reverse of `period`.
+fn read_period(value: &str) -> (Option<time::Date>, Option<time::Date>) {
+    match value.split_once(" - ") {
+        Some((start, end)) => (read_date(start), read_date(end)),
+        None => (None, read_date(value)),
+    }
+}
+
+fn read_date(value: &str) -> Option<time::Date> {
+    if value.trim().is_empty() {
+        return None;
+    }
+
+    time::Date::parse(value.trim(), MYLIB_DATE_FORMAT).map_or_else(
+        |cause| {
+            log::warn!(target: "mylib", "Invalid date '{}': {}", value, cause);
+
+            None
+        },
+        Some)
+}
+
+fn read_isbn(value: &str) -> Option<Isbn> {
+    if value.is_empty() {
+        return None;
+    }
+
+    Isbn::parse(value).map_or_else(
+        |cause| {
+            log::warn!(target: "mylib", "Invalid ISBN '{}': {}", value, cause);
+
+            None
+        },
+        Some)
+}
+
+/// Writes the cover as JSON line in the Mylib images,
+/// and returns its content type.
+fn write_cover<A: Write>(
+    cover: Cover,
+    hashcode: i32,
+    img_writer: &mut A,
+) -> Result<String> {
+    let json_cover = json::object!{
+        base64Image: base64::encode(&cover.data),
+        elementHashcode: hashcode,
+        imageOrientation: 0,
+        type: "BOOK",
+    };
+
+    let line = json::stringify(json_cover) + "\r\n";
+
+    img_writer.write_all(line.as_bytes()).map(|_| cover.content_type)
+}
+
+const JAVA_HASH_SEED: i32 = 31;
+
+fn java_hashcode(str: String) -> i32 {
+    let mut h: i32 = 0;
+    
+    for ch in str.chars() {
+        h = JAVA_HASH_SEED.wrapping_mul(h).wrapping_add(ch as i32);
+    }
+
+    h
+}
+
+fn book_hashcode(title: &str, authors: &[String]) -> Result<i32> {
+    let string_repr = authors.first().map_or_else(
+        || Err(Error::other("Missing author")),
+        |author| Ok(format!("{}{}", title, author)));
+
+    string_repr.map(java_hashcode)
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unnecessary_cast)]
+    fn test_java_hashcode() {
+        assert_eq!((2457 as i32), java_hashcode("Le".to_string()));
+        assert_eq!((65282059 as i32), java_hashcode("Codex".to_string()));
+        assert_eq!((816537616 as i32), java_hashcode("Guta-Sintram".to_string()));
+        assert_eq!((100742 as i32), java_hashcode("est".to_string()));
+        assert_eq!((3737 as i32), java_hashcode("un".to_string()));
+        assert_eq!((109904722 as i32), java_hashcode("manuscrit".to_string()));
+        assert_eq!((1974693833 as i32), java_hashcode("enluminé".to_string()));
+        assert_eq!((3076146 as i32), java_hashcode("daté".to_string()));
+        assert_eq!((3201 as i32), java_hashcode("de".to_string()));
+        assert_eq!((1508543 as i32), java_hashcode("1154".to_string()));
+        assert_eq!((-1197664643 as i32), java_hashcode("exécuté".to_string()));
+        assert_eq!((224 as i32), java_hashcode("à".to_string()));
+        assert_eq!((-1926557657 as i32), java_hashcode("l'abbaye".to_string()));
+        assert_eq!((3201 as i32), java_hashcode("de".to_string()));
+        assert_eq!((-1791335646 as i32), java_hashcode("Marbach".to_string()));
+        assert_eq!((3241 as i32), java_hashcode("en".to_string()));
+        assert_eq!((759338387 as i32), java_hashcode("Alsace.".to_string()));
+        assert_eq!((76995002 as i32), java_hashcode("Peint".to_string()));
+        assert_eq!((3075842 as i32), java_hashcode("dans".to_string()));
+        assert_eq!((3449 as i32), java_hashcode("le".to_string()));
+        assert_eq!((109780401 as i32), java_hashcode("style".to_string()));
+        assert_eq!((-925389361 as i32), java_hashcode("roman,".to_string()));
+        assert_eq!((3363 as i32), java_hashcode("il".to_string()));
+        assert_eq!((100742 as i32), java_hashcode("est".to_string()));
+        assert_eq!((-28034847 as i32), java_hashcode("actuellement".to_string()));
+        assert_eq!((-568241327 as i32), java_hashcode("conservé".to_string()));
+        assert_eq!((224 as i32), java_hashcode("à".to_string()));
+        assert_eq!((3445 as i32), java_hashcode("la".to_string()));
+        assert_eq!((1977356260 as i32), java_hashcode("Bibliothèque".to_string()));
+        assert_eq!((3217 as i32), java_hashcode("du".to_string()));
+        assert_eq!((69062892 as i32), java_hashcode("Grand".to_string()));
+        assert_eq!((1713345047 as i32), java_hashcode("séminaire".to_string()));
+        assert_eq!((3201 as i32), java_hashcode("de".to_string()));
+        assert_eq!((2049634778 as i32), java_hashcode("Strasbourg".to_string()));
+        assert_eq!((-347316087 as i32), java_hashcode("(Ms.37).".to_string()));
+    }
+
+    #[test]
+    #[allow(clippy::unnecessary_to_owned, clippy::useless_vec)]
+    fn test_book_hashcode() {
+        assert_eq!(1663365717, book_hashcode(
+            &"Japon, Miscellanées".to_string(),
+            &vec![
+                "Chantal DELTENRE".to_string(),
+                "Maximilien DAUBER".to_string()
+            ]).unwrap());
+
+        assert_eq!(49491434, book_hashcode(
+            &"Ainsi Parlait Zarathoustra".to_string(),
+            &vec![ "Friedrich Wilhelm Nietzsche".to_string() ]).unwrap());
+
+        assert_eq!(1763833006, book_hashcode(
+            &"Alien Earth".to_string(),
+            &vec![ "Robin Hobb".to_string() ]).unwrap());
+
+        assert_eq!(-1061641663, book_hashcode(
+            &"Allez les mages !".to_string(),
+            &vec![ "Terry Pratchett".to_string() ]).unwrap());
+
+        assert_eq!(-1648148861, book_hashcode(
+            &"Ally".to_string(),
+            &vec![ "Karen Traviss".to_string() ]).unwrap());
+
+        assert_eq!(1397285980, book_hashcode(
+            &"Va-t-en-guerre".to_string(),
+            &vec![ "Terry Pratchett".to_string() ]).unwrap());
+
+        assert_eq!(411805042, book_hashcode(
+            &"Vision aveugle".to_string(),
+            &vec![ "Peter Watts".to_string() ]).unwrap());
+
+        assert_eq!(2094868745, book_hashcode(
+            &"Vulture Peak".to_string(),
+            &vec![ "John Burdett".to_string() ]).unwrap());
+
+        assert_eq!(-1547775297, book_hashcode(
+            &"Échopraxie".to_string(),
+            &vec![ "Peter Watts".to_string() ]).unwrap());
+
+        assert_eq!(-1492802074, book_hashcode(
+            &"Élévation".to_string(),
+            &vec![ "David Brin".to_string() ]).unwrap());
+
+    }
+
+    #[test]
+    fn test_book_hashcode_co_authors() {
+        let input = "<books version=\"2\">
+  <book>
+    <title>Japon, Miscellanées</title>
+    <authors>
+      <author><name>Chantal DELTENRE</name></author>
+      <author><name>Maximilien DAUBER</name></author>
+    </authors>
+  </book>
+</books>";
+
+        let book = crate::codex::util::parse(input.as_bytes()).
+            next().unwrap().unwrap();
+
+        let authors: Vec<String> =
+            book.authors.iter().map(|a| a.name.to_string()).collect();
+
+        assert_eq!(authors.join(", "), "Chantal DELTENRE, Maximilien DAUBER");
+
+        assert_eq!(1663365717, book_hashcode(&book.title, &authors).unwrap());
+    }
+
+    #[test]
+    fn test_serie() {
+        let mut book = crate::codex::empty_book();
+
+        assert_eq!(serie(&book), "");
+
+        book.volume = Some(3);
+
+        assert_eq!(serie(&book), "");
+
+        book.series = "Les Annales du Disque-monde".to_string();
+
+        assert_eq!(serie(&book), "Les Annales du Disque-monde #3");
+
+        book.volume = None;
+
+        assert_eq!(serie(&book), "Les Annales du Disque-monde");
+    }
+
+    #[test]
+    fn test_period() {
+        let mut book = crate::codex::empty_book();
+
+        assert_eq!(period(&book), "");
+
+        book.read_start = Some(time::date!(2020-08-01));
+
+        assert_eq!(period(&book), "01/08/2020 - ");
+
+        book.read_end = Some(time::date!(2020-08-15));
+
+        assert_eq!(period(&book), "01/08/2020 - 15/08/2020");
+
+        book.read_start = None;
+
+        assert_eq!(period(&book), " - 15/08/2020");
+    }
+
+    #[test]
+    fn test_write_personal_columns() {
+        let mut book = crate::codex::empty_book();
+
+        book.title = "Va-t-en-guerre".to_string();
+        book.series = "Les Annales du Disque-monde".to_string();
+        book.volume = Some(21);
+        book.read = true;
+        book.read_start = Some(time::date!(2020-08-01));
+        book.read_end = Some(time::date!(2020-08-15));
+        book.notes = "Dédicacé".to_string();
+
+        let mut cw = csv::WriterBuilder::new().
+            delimiter(b';').
+            quote_style(csv::QuoteStyle::NonNumeric).
+            from_writer(vec![]);
+
+        let mut img: Vec<u8> = vec![];
+
+        write(&mut cw, &mut img, &book, &Dialect::default(),
+              DEFAULT_COVER_DIRECTORY,
+              None).unwrap();
+
+        let out = String::from_utf8(cw.into_inner().unwrap()).unwrap();
+
+        assert_eq!(out, "\"Va-t-en-guerre\";\"\";\"Les Annales du Disque-monde #21\";\"\";\"\";\"\";0;\"\";1;\"01/08/2020 - 15/08/2020\";\"Dédicacé\";\"\";\"\"\n");
+        assert!(img.is_empty());
+    }
+
+    fn mylib_book() -> Book {
+        let mut book = empty_book();
+
+        book.title = "Accros du roc".to_string();
+        book.authors = vec![ Author {
+            first_name: "Terry".to_string(),
+            last_name: "Pratchett".to_string(),
+            name: "Terry Pratchett".to_string(),
+        } ];
+        book.series = "Les Annales du Disque-monde".to_string();
+        book.volume = Some(16);
+        book.kind = vec![ "Science Fiction".to_string(), "Fiction".to_string() ];
+        book.pubdate = Some(time::date!(2012-07-10));
+        book.publisher = "Pocket".to_string();
+        book.pages = 411;
+        book.isbn = vec![ Isbn::Isbn13(9782266211963) ];
+        book.read = true;
+        book.read_start = Some(time::date!(2020-08-01));
+        book.read_end = Some(time::date!(2020-08-15));
+        book.notes = "Dédicacé; \"collector\"".to_string();
+        book.summary = "Suzanne est une jeune étudiante discrète ...".to_string();
+
+        book
+    }
+
+    #[test]
+    fn test_read_round_trip() {
+        let book = mylib_book();
+
+        let mut cw = csv::WriterBuilder::new().
+            delimiter(b';').
+            quote_style(csv::QuoteStyle::NonNumeric).
+            from_writer(vec![]);
+
+        let mut img: Vec<u8> = vec![];
+
+        write(&mut cw, &mut img, &book, &Dialect::default(),
+              DEFAULT_COVER_DIRECTORY,
+              None).unwrap();
+
+        let out = cw.into_inner().unwrap();
+
+        let books: Vec<Book> = read(out.as_slice(), img.as_slice(), &Dialect::default()).unwrap().
+            map(|b| b.unwrap()).collect();
+
+        let mut expected = mylib_book();
+
+        expected.isbn.push(Isbn::Isbn10("226621196X".to_string()));
+
+        assert_eq!(books, vec![ expected ]);
+
+        // Mylib -> Codex XML -> Codex parser
+        let mut w = crate::codex::util::BookWriter::new(vec![]).unwrap();
+
+        w.write(&books[0]).unwrap();
+
+        let xml = w.finish().unwrap();
+
+        let parsed: Vec<Book> = crate::codex::util::parse(xml.as_slice()).
+            map(|b| b.unwrap()).collect();
+
+        assert_eq!(parsed, books);
+    }
+
+    #[test]
+    fn test_dialect_round_trip() {
+        let dialect = Dialect::parse(r#"
+delimiter = ","
+quoting = "necessary"
+encoding = "windows-1252"
+header = true
+line-ending = "crlf"
+
+[[columns]]
+name = "Titre"
+field = "title"
+
+[[columns]]
+name = "Auteurs"
+field = "authors"
+
+[[columns]]
+name = "Emplacement"
+
+[[columns]]
+name = "ISBN"
+field = "isbn10"
+
+[[columns]]
+name = "Note"
+field = "rating"
+
+[[columns]]
+name = "Tri"
+template = "{authors|last_first|join \"; \"|upper}{pubdate? \" (\" pubdate|%Y \")\"}"
+"#).unwrap();
+
+        let mut book = mylib_book();
+
+        book.title = "Échopraxie, tome 2".to_string();
+        book.rating = Some(4.5);
+
+        let mut cw = csv_writer(vec![], &dialect).unwrap();
+        let mut img: Vec<u8> = vec![];
+
+        write(&mut cw, &mut img, &book, &dialect, DEFAULT_COVER_DIRECTORY,
+              None).unwrap();
+
+        let out = cw.into_inner().unwrap();
+
+        assert_eq!(out, b"Titre,Auteurs,Emplacement,ISBN,Note,Tri\r\n\
+                          \"\xC9chopraxie, tome 2\",Terry Pratchett,,226621196X,4.5,\
+                          \"PRATCHETT, TERRY (2012)\"\r\n");
+
+        let books: Vec<Book> = read(out.as_slice(), img.as_slice(), &dialect).
+            unwrap().map(|b| b.unwrap()).collect();
+
+        assert_eq!(books.len(), 1);
+        assert_eq!(books[0].title, book.title);
+        assert_eq!(books[0].authors, book.authors);
+        assert_eq!(books[0].rating, Some(4.5));
+        assert_eq!(books[0].isbn, vec![ Isbn::Isbn13(9782266211963),
+                                        Isbn::Isbn10("226621196X".to_string()) ]);
+    }
+
+    #[test]
+    fn test_read_covers() {
+        let csv = "\"Accros du roc\";\"Terry Pratchett\";\"\";\"\";\"\";\"\";0;\"\";0;\"\";\"\";\"\";\"/MyLibrary/Images/Books/9782266211963.png\"
+\"Ally\";\"Karen Traviss\";\"\";\"\";\"\";\"\";0;\"\";0;\"\";\"\";\"\";\"https://covers/ally.jpg\"
+\"Vulture Peak\";\"John Burdett\";\"\";\"\";\"\";\"\";0;\"\";0;\"\";\"\";\"\";\"/MyLibrary/Images/Books/missing.jpg\"
+";
+
+        let h = book_hashcode(
+            "Accros du roc", &[ "Terry Pratchett".to_string() ]).unwrap();
+
+        let images = format!(
+            "{}\r\n",
+            json::stringify(json::object!{
+                base64Image: "iVBORw0KGgo=",
+                elementHashcode: h,
+                imageOrientation: 0,
+                type: "BOOK",
+            }));
+
+        let covers: Vec<String> = read(csv.as_bytes(), images.as_bytes(), &Dialect::default()).
+            unwrap().map(|b| b.unwrap().cover).collect();
+
+        assert_eq!(covers, vec![
+            "data:image/png;base64,iVBORw0KGgo=".to_string(),
+            "https://covers/ally.jpg".to_string(),
+            "".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn test_read_invalid_columns() {
+        let res: Vec<Result<Book>> =
+            read("\"Title\";\"Author\"\n".as_bytes(), std::io::empty(),
+                 &Dialect::default()).
+            unwrap().collect();
+
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].as_ref().unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_write_isbn10_as_isbn13() {
+        let mut book = crate::codex::empty_book();
+
+        book.title = "Accros du roc".to_string();
+        book.isbn = vec![ Isbn::Isbn10("226621196X".to_string()) ];
+
+        let mut cw = csv::WriterBuilder::new().
+            delimiter(b';').
+            from_writer(vec![]);
+
+        let mut img: Vec<u8> = vec![];
+
+        write(&mut cw, &mut img, &book, &Dialect::default(),
+              DEFAULT_COVER_DIRECTORY,
+              None).unwrap();
+
+        let out = String::from_utf8(cw.into_inner().unwrap()).unwrap();
+
+        assert_eq!(out, "Accros du roc;;;;;;0;9782266211963;0;;;;\n");
+    }
+
+    #[test]
+    fn test_write_failed_cover() {
+        let mut book = mylib_book();
+
+        book.cover = "http://covers.invalid/accros.jpg".to_string();
+
+        let mut cw = csv::WriterBuilder::new().
+            delimiter(b';').
+            from_writer(vec![]);
+
+        let mut img: Vec<u8> = vec![];
+
+        let failure = write(
+            &mut cw, &mut img, &book, &Dialect::default(), DEFAULT_COVER_DIRECTORY,
+            Some(Err(Error::new(ErrorKind::Interrupted, "timeout")))).
+            unwrap();
+
+        assert_eq!(failure.map(|e| e.kind()), Some(ErrorKind::Interrupted));
+
+        let out = String::from_utf8(cw.into_inner().unwrap()).unwrap();
+
+        // Empty cover column, rather than an invalid path
+        assert!(out.ends_with(";\n"));
+        assert!(img.is_empty());
+    }
+}
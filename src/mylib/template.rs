@@ -0,0 +1,772 @@
+//! Templates of the Mylib columns over the book fields, e.g.
+//! `{title}{subtitle? ": " subtitle}`, `{authors|last_first|join "; "}`
+//! or `{pubdate|%Y}`.
+//!
+//! Outside the braces, the text is kept as is (`{{` and `}}` standing for
+//! the braces themselves). Inside, either a field followed by filters
+//! (`field|filter|...`), or a condition `field? ...` rendering the following
+//! strings and fields only if the first field is not empty.
+
+use std::fmt::{Debug, Formatter, Write};
+use std::io::{Error, ErrorKind, Result};
+
+use crate::codex::{Author, Book};
+
+use super::{MYLIB_DATE_FORMAT, MYLIB_FALSE, MYLIB_TRUE};
+
+/// Type of the values, checked when the template is parsed
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Text,
+    List,
+    Date,
+    Authors,
+    Bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Var {
+    Title,
+    Subtitle,
+    Authors,
+    Categories,
+    PubDate,
+    Publisher,
+    Pages,
+    Isbn,
+    Isbn10,
+    Summary,
+    Cover,
+    Language,
+    Series,
+    Volume,
+    Format,
+    Rating,
+    Read,
+    ReadStart,
+    ReadEnd,
+    LoanedTo,
+    Notes,
+    Tags,
+}
+
+/// Names of the fields, as in `codex::Book`
+/// (or as the Codex XML elements)
+const VARS: [(&str, Var); 29] = [
+    ("title", Var::Title),
+    ("subtitle", Var::Subtitle),
+    ("authors", Var::Authors),
+    ("kind", Var::Categories),
+    ("categories", Var::Categories),
+    ("pubdate", Var::PubDate),
+    ("publishDate", Var::PubDate),
+    ("publisher", Var::Publisher),
+    ("pages", Var::Pages),
+    ("pageCount", Var::Pages),
+    ("isbn", Var::Isbn),
+    ("isbn10", Var::Isbn10),
+    ("summary", Var::Summary),
+    ("description", Var::Summary),
+    ("cover", Var::Cover),
+    ("language", Var::Language),
+    ("series", Var::Series),
+    ("volume", Var::Volume),
+    ("format", Var::Format),
+    ("rating", Var::Rating),
+    ("read", Var::Read),
+    ("read_start", Var::ReadStart),
+    ("readStartDate", Var::ReadStart),
+    ("read_end", Var::ReadEnd),
+    ("readEndDate", Var::ReadEnd),
+    ("loaned_to", Var::LoanedTo),
+    ("loanedTo", Var::LoanedTo),
+    ("notes", Var::Notes),
+    ("tags", Var::Tags),
+];
+
+impl Var {
+    fn kind(self) -> Kind {
+        match self {
+            Var::Authors => Kind::Authors,
+            Var::Categories | Var::Tags => Kind::List,
+            Var::PubDate | Var::ReadStart | Var::ReadEnd => Kind::Date,
+            Var::Read => Kind::Bool,
+            _ => Kind::Text,
+        }
+    }
+
+    fn value<'a>(self, ctx: &Context<'a>) -> Value<'a> {
+        let book = ctx.book;
+        let text = |s: &str| Value::Text(s.to_string());
+        let number = |n: Option<String>| Value::Text(n.unwrap_or_default());
+
+        match self {
+            Var::Title => text(&book.title),
+            Var::Subtitle => text(&book.subtitle),
+            Var::Authors => Value::Authors(&book.authors),
+            Var::Categories => Value::List(book.kind.clone()),
+            Var::PubDate => Value::Date(book.pubdate),
+            Var::Publisher => text(&book.publisher),
+            Var::Pages => number(Some(book.pages).
+                                 filter(|p| *p > 0).map(|p| p.to_string())),
+            Var::Isbn => Value::Text(super::isbn13(book)),
+            Var::Isbn10 => Value::Text(
+                super::field_value(book, super::Field::Isbn10)),
+            Var::Summary => text(&book.summary),
+            Var::Cover => text(ctx.cover),
+            Var::Language => text(&book.language),
+            Var::Series => text(&book.series),
+            Var::Volume => number(book.volume.map(|v| v.to_string())),
+            Var::Format => text(&book.format),
+            Var::Rating => number(book.rating.map(|r| r.to_string())),
+            Var::Read => Value::Bool(book.read),
+            Var::ReadStart => Value::Date(book.read_start),
+            Var::ReadEnd => Value::Date(book.read_end),
+            Var::LoanedTo => text(&book.loaned_to),
+            Var::Notes => text(&book.notes),
+            Var::Tags => Value::List(book.tags.clone()),
+        }
+    }
+}
+
+enum Value<'a> {
+    Text(String),
+    List(Vec<String>),
+    Date(Option<time::Date>),
+    Authors(&'a [Author]),
+    Bool(bool),
+}
+
+impl Value<'_> {
+    fn is_empty(&self) -> bool {
+        match self {
+            Value::Text(s) => s.is_empty(),
+            Value::List(l) => l.is_empty(),
+            Value::Date(d) => d.is_none(),
+            Value::Authors(a) => a.is_empty(),
+            Value::Bool(b) => !b,
+        }
+    }
+
+    /// Returns the value as in the Mylib columns.
+    fn render(self) -> String {
+        match self {
+            Value::Text(s) => s,
+            Value::List(l) => l.join(", "),
+            Value::Date(d) => d.
+                map(|d| d.format(MYLIB_DATE_FORMAT)).unwrap_or_default(),
+            Value::Authors(a) => a.iter().
+                map(|a| a.name.to_string()).collect::<Vec<String>>().join(", "),
+            Value::Bool(b) => (if b { MYLIB_TRUE } else { MYLIB_FALSE }).
+                to_string(),
+        }
+    }
+}
+
+// ---
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Upper,
+    Lower,
+    /// Uppercase first letter of each word
+    Capitalize,
+    Trim,
+    /// Keeps at most the given number of characters
+    Truncate(usize),
+    /// Same as `Truncate`, appending "..." if truncated
+    Ellipsis(usize),
+    /// Keeps the first item of a list
+    First,
+    Join(String),
+    /// Replaces an empty value
+    Default(String),
+    /// Authors as "Last, First"
+    LastFirst,
+    /// Authors as "First Last"
+    FirstLast,
+    /// Date format (e.g. `%Y`, or `format "%d %B %Y"` with spaces)
+    DateFormat(String),
+}
+
+const FILTERS: &str = "upper, lower, capitalize, trim, truncate <n>, \
+                       ellipsis <n>, first, join \"<separator>\", \
+                       default \"<text>\", last_first, first_last, \
+                       %<format>, format \"<format>\"";
+
+impl Filter {
+    /// Returns the kind of the filtered values, if the filter applies.
+    fn output(&self, kind: Kind) -> Option<Kind> {
+        match (self, kind) {
+            (Filter::Upper, Kind::Text | Kind::List) |
+            (Filter::Lower, Kind::Text | Kind::List) |
+            (Filter::Capitalize, Kind::Text | Kind::List) |
+            (Filter::Trim, Kind::Text | Kind::List) => Some(kind),
+
+            (Filter::Truncate(_), Kind::Text) |
+            (Filter::Ellipsis(_), Kind::Text) => Some(Kind::Text),
+
+            (Filter::First, Kind::List) => Some(Kind::Text),
+            (Filter::First, Kind::Authors) => Some(Kind::Authors),
+
+            (Filter::Join(_), Kind::List | Kind::Authors) => Some(Kind::Text),
+
+            (Filter::Default(_), _) => Some(Kind::Text),
+
+            (Filter::LastFirst, Kind::Authors) |
+            (Filter::FirstLast, Kind::Authors) => Some(Kind::List),
+
+            (Filter::DateFormat(_), Kind::Date) => Some(Kind::Text),
+
+            _ => None,
+        }
+    }
+
+    fn apply<'a>(&self, value: Value<'a>) -> Value<'a> {
+        let each = |value: Value<'a>, f: &dyn Fn(&str) -> String| match value {
+            Value::Text(s) => Value::Text(f(&s)),
+            Value::List(l) => Value::List(l.iter().map(|s| f(s)).collect()),
+            v => v,
+        };
+
+        match (self, value) {
+            (Filter::Upper, v) => each(v, &|s| s.to_uppercase()),
+            (Filter::Lower, v) => each(v, &|s| s.to_lowercase()),
+            (Filter::Capitalize, v) => each(v, &capitalize),
+            (Filter::Trim, v) => each(v, &|s| s.trim().to_string()),
+
+            (Filter::Truncate(n), Value::Text(s)) =>
+                Value::Text(s.chars().take(*n).collect()),
+
+            (Filter::Ellipsis(n), Value::Text(s)) =>
+                Value::Text(crate::codex::ellipsis(&s, *n)),
+
+            (Filter::First, Value::List(l)) =>
+                Value::Text(l.into_iter().next().unwrap_or_default()),
+
+            (Filter::First, Value::Authors(a)) =>
+                Value::Authors(&a[..a.len().min(1)]),
+
+            (Filter::Join(sep), Value::List(l)) => Value::Text(l.join(sep)),
+
+            (Filter::Join(sep), Value::Authors(a)) => Value::Text(
+                a.iter().map(|a| a.name.to_string()).
+                    collect::<Vec<String>>().join(sep)),
+
+            (Filter::Default(d), v) if v.is_empty() => Value::Text(d.to_string()),
+            (Filter::Default(_), v) => Value::Text(v.render()),
+
+            (Filter::LastFirst, Value::Authors(a)) =>
+                Value::List(a.iter().map(last_first).collect()),
+
+            (Filter::FirstLast, Value::Authors(a)) =>
+                Value::List(a.iter().map(|a| a.name.to_string()).collect()),
+
+            // Format checked by `date_format` (a formatting error being empty)
+            (Filter::DateFormat(f), Value::Date(d)) => Value::Text(d.and_then(|d| {
+                let mut text = String::new();
+
+                write!(text, "{}", d.lazy_format(f)).ok().map(|_| text)
+            }).unwrap_or_default()),
+
+            // Kinds checked by `output`
+            (_, v) => v,
+        }
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut upper = true;
+
+    text.chars().map(|c| {
+        let s: String = if upper {
+            c.to_uppercase().collect()
+        } else {
+            c.to_lowercase().collect()
+        };
+
+        upper = !c.is_alphanumeric() && c != '\'';
+
+        s
+    }).collect()
+}
+
+/// Returns "Last, First", or the full name if not split.
+fn last_first(author: &Author) -> String {
+    if author.last_name.is_empty() {
+        match author.name.rsplit_once(' ') {
+            Some((first, last)) => format!("{}, {}", last, first),
+            None => author.name.to_string(),
+        }
+    } else if author.first_name.is_empty() {
+        author.last_name.to_string()
+    } else {
+        format!("{}, {}", author.last_name, author.first_name)
+    }
+}
+
+// ---
+
+#[derive(Debug, Clone, PartialEq)]
+struct Pipeline {
+    var: Var,
+    filters: Vec<Filter>,
+}
+
+impl Pipeline {
+    fn evaluate<'a>(&self, ctx: &Context<'a>) -> Value<'a> {
+        self.filters.iter().fold(self.var.value(ctx), |v, f| f.apply(v))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Item {
+    Text(String),
+    Pipeline(Pipeline),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Pipeline(Pipeline),
+    /// Items rendered only if the value is not empty
+    Condition(Pipeline, Vec<Item>),
+}
+
+/// Book being written, with its Mylib cover path
+pub struct Context<'a> {
+    pub book: &'a Book,
+    pub cover: &'a str,
+}
+
+/// Template of a Mylib column (see the module documentation)
+#[derive(Clone)]
+pub struct Template {
+    source: String,
+    parts: Vec<Part>,
+}
+
+impl Debug for Template {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "Template({:?})", self.source)
+    }
+}
+
+impl PartialEq for Template {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Template> {
+        parse_parts(source).map(|parts| Template {
+            source: source.to_string(),
+            parts,
+        }).map_err(|(column, message)| Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid template '{}' at {}: {}",
+                    source, column + 1, message)))
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn render(&self, ctx: &Context) -> String {
+        let mut out = String::new();
+
+        for part in &self.parts {
+            match part {
+                Part::Text(s) => out.push_str(s),
+
+                Part::Pipeline(p) => out.push_str(&p.evaluate(ctx).render()),
+
+                Part::Condition(p, items) if !p.evaluate(ctx).is_empty() => {
+                    for item in items {
+                        match item {
+                            Item::Text(s) => out.push_str(s),
+                            Item::Pipeline(p) =>
+                                out.push_str(&p.evaluate(ctx).render()),
+                        }
+                    }
+                }
+
+                Part::Condition(_, _) => (),
+            }
+        }
+
+        out
+    }
+}
+
+// --- Parsing
+
+/// Parse error: 0-based character index and message
+type ParseError = (usize, String);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Number(usize),
+    Format(String),
+    Pipe,
+    Question,
+}
+
+fn parse_parts(source: &str) -> std::result::Result<Vec<Part>, ParseError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut parts = vec![];
+    let mut text = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match (chars[i], chars.get(i + 1)) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                text.push(chars[i]);
+                i += 2;
+            }
+
+            ('{', _) => {
+                if !text.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                }
+
+                let (tokens, end) = lex(&chars, i + 1)?;
+
+                parts.push(parse_expression(&tokens, i)?);
+                i = end + 1;
+            }
+
+            ('}', _) => return Err((i, "Unexpected '}' (use '}}')".to_string())),
+
+            (c, _) => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+
+    Ok(parts)
+}
+
+/// Returns the tokens of the expression starting at `start`,
+/// and the index of the closing brace.
+fn lex(
+    chars: &[char],
+    start: usize,
+) -> std::result::Result<(Vec<Token>, usize), ParseError> {
+    let mut tokens = vec![];
+    let mut i = start;
+
+    let word = |i: usize, stop: &dyn Fn(char) -> bool| {
+        let mut end = i;
+
+        while end < chars.len() && !stop(chars[end]) {
+            end += 1;
+        }
+
+        (chars[i..end].iter().collect::<String>(), end)
+    };
+
+    loop {
+        match chars.get(i) {
+            None => return Err((start - 1, "Missing '}'".to_string())),
+
+            Some('}') => return Ok((tokens, i)),
+
+            Some(c) if c.is_whitespace() => i += 1,
+
+            Some('|') => {
+                tokens.push(Token::Pipe);
+                i += 1;
+            }
+
+            Some('?') => {
+                tokens.push(Token::Question);
+                i += 1;
+            }
+
+            Some('"') => {
+                let mut s = String::new();
+
+                i += 1;
+
+                loop {
+                    match (chars.get(i), chars.get(i + 1)) {
+                        (None, _) => return Err((i, "Missing '\"'".to_string())),
+
+                        (Some('\\'), Some(c)) => {
+                            s.push(*c);
+                            i += 2;
+                        }
+
+                        (Some('"'), _) => break,
+
+                        (Some(c), _) => {
+                            s.push(*c);
+                            i += 1;
+                        }
+                    }
+                }
+
+                tokens.push(Token::Str(s));
+                i += 1;
+            }
+
+            Some('%') => {
+                let (f, end) = word(
+                    i, &|c| c == '|' || c == '}' || c.is_whitespace());
+
+                tokens.push(Token::Format(f));
+                i = end;
+            }
+
+            Some(c) if c.is_ascii_digit() => {
+                let (n, end) = word(i, &|c| !c.is_ascii_digit());
+
+                tokens.push(Token::Number(n.parse().map_err(
+                    |_| (i, format!("Invalid number '{}'", n)))?));
+                i = end;
+            }
+
+            Some(c) if c.is_alphabetic() || *c == '_' => {
+                let (name, end) = word(
+                    i, &|c| !(c.is_alphanumeric() || c == '_'));
+
+                tokens.push(Token::Ident(name));
+                i = end;
+            }
+
+            Some(c) => return Err((i, format!("Unexpected '{}'", c))),
+        }
+    }
+}
+
+fn parse_expression(
+    tokens: &[Token],
+    at: usize,
+) -> std::result::Result<Part, ParseError> {
+    let error = |message: String| (at, message);
+
+    match tokens {
+        [ Token::Ident(_), Token::Question, items @ .. ] => {
+            let (cond, _) = parse_pipeline(&tokens[..1]).map_err(error)?;
+            let mut rest = items;
+            let mut parsed = vec![];
+
+            while let Some(token) = rest.first() {
+                match token {
+                    Token::Str(s) => {
+                        parsed.push(Item::Text(s.to_string()));
+                        rest = &rest[1..];
+                    }
+
+                    _ => {
+                        let (p, next) = parse_pipeline(rest).map_err(error)?;
+
+                        parsed.push(Item::Pipeline(p));
+                        rest = next;
+                    }
+                }
+            }
+
+            Ok(Part::Condition(cond, parsed))
+        }
+
+        _ => match parse_pipeline(tokens).map_err(error)? {
+            (p, []) => Ok(Part::Pipeline(p)),
+            (_, rest) => Err(error(format!("Unexpected {:?}", rest[0]))),
+        },
+    }
+}
+
+/// Parses a field with its filters,
+/// returning the remaining tokens.
+fn parse_pipeline(
+    tokens: &[Token],
+) -> std::result::Result<(Pipeline, &[Token]), String> {
+    let var = match tokens.first() {
+        Some(Token::Ident(name)) => VARS.iter().find(|(n, _)| n == name).
+            map(|(_, v)| *v).ok_or_else(|| format!("Unknown field '{}'", name))?,
+
+        Some(t) => return Err(format!("Expected a field, found {:?}", t)),
+
+        None => return Err("Expected a field".to_string()),
+    };
+
+    let mut kind = var.kind();
+    let mut filters = vec![];
+    let mut rest = &tokens[1..];
+
+    while let Some(Token::Pipe) = rest.first() {
+        let (filter, consumed) = match &rest[1..] {
+            [ Token::Format(f), .. ] => (Filter::DateFormat(date_format(f)?), 1),
+
+            [ Token::Ident(name), args @ .. ] => match (name.as_str(), args) {
+                ("upper", _) => (Filter::Upper, 1),
+                ("lower", _) => (Filter::Lower, 1),
+                ("capitalize", _) => (Filter::Capitalize, 1),
+                ("trim", _) => (Filter::Trim, 1),
+                ("first", _) => (Filter::First, 1),
+                ("last_first", _) => (Filter::LastFirst, 1),
+                ("first_last", _) => (Filter::FirstLast, 1),
+
+                ("truncate", [ Token::Number(n), .. ]) =>
+                    (Filter::Truncate(*n), 2),
+
+                ("ellipsis", [ Token::Number(n), .. ]) =>
+                    (Filter::Ellipsis(*n), 2),
+
+                ("join", [ Token::Str(s), .. ]) =>
+                    (Filter::Join(s.to_string()), 2),
+
+                ("default", [ Token::Str(s), .. ]) =>
+                    (Filter::Default(s.to_string()), 2),
+
+                ("format", [ Token::Str(s), .. ]) =>
+                    (Filter::DateFormat(date_format(s)?), 2),
+
+                _ => return Err(format!(
+                    "Invalid filter '{}' (expected {})", name, FILTERS)),
+            },
+
+            _ => return Err(format!("Expected a filter ({})", FILTERS)),
+        };
+
+        kind = filter.output(kind).ok_or_else(|| format!(
+            "Filter {:?} cannot apply to {:?}", filter, kind))?;
+
+        filters.push(filter);
+        rest = &rest[1 + consumed..];
+    }
+
+    Ok((Pipeline { var, filters }, rest))
+}
+
+/// Checks the date format, only accepting the date specifiers
+/// (e.g. `%d/%m/%Y`, but not `%H`).
+fn date_format(format: &str) -> std::result::Result<String, String> {
+    let mut chars = format.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+
+        let spec = match chars.next() {
+            Some('-') | Some('_') | Some('0') => chars.next(),
+            s => s,
+        };
+
+        match spec {
+            Some('a' | 'A' | 'b' | 'B' | 'C' | 'd' | 'D' | 'F' | 'g' | 'G' |
+                 'j' | 'm' | 'u' | 'U' | 'V' | 'w' | 'W' | 'y' | 'Y' | '%') => (),
+
+            Some(s) => return Err(format!(
+                "Invalid date specifier '%{}' in '{}'", s, format)),
+
+            None => return Err(format!(
+                "Incomplete date specifier in '{}' (%% for '%')", format)),
+        }
+    }
+
+    Ok(format.to_string())
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::codex::empty_book;
+
+    fn book() -> Book {
+        let mut book = empty_book();
+
+        book.title = "Accros du roc".to_string();
+        book.subtitle = "Les Annales du Disque-monde".to_string();
+        book.authors = vec![
+            Author {
+                first_name: "Terry".to_string(),
+                last_name: "Pratchett".to_string(),
+                name: "Terry Pratchett".to_string(),
+            },
+            Author {
+                first_name: "".to_string(),
+                last_name: "".to_string(),
+                name: "Neil Gaiman".to_string(),
+            },
+        ];
+        book.kind = vec![ "Fantasy".to_string(), "humour".to_string() ];
+        book.pubdate = Some(time::date!(2012-07-10));
+        book.read = true;
+
+        book
+    }
+
+    fn render(template: &str, book: &Book) -> String {
+        Template::parse(template).unwrap().
+            render(&Context { book, cover: "/covers/accros.jpg" })
+    }
+
+    #[test]
+    fn test_render() {
+        let mut b = book();
+
+        assert_eq!(render("{title}{subtitle? \": \" subtitle}", &b),
+                   "Accros du roc: Les Annales du Disque-monde");
+
+        assert_eq!(render("{authors|last_first|join \"; \"}", &b),
+                   "Pratchett, Terry; Gaiman, Neil");
+
+        assert_eq!(render("{pubdate|%Y}", &b), "2012");
+        assert_eq!(render("{pubdate? \"(\" pubdate|%Y \")\"}", &b), "(2012)");
+        assert_eq!(render("{pubdate|format \"%d %m %Y\"}", &b), "10 07 2012");
+        assert_eq!(render("{pubdate|format \"%a %-d %b %y (%%)\"}", &b),
+                   "Tue 10 Jul 12 (%)");
+        assert_eq!(render("{pubdate} ({read})", &b), "10/07/2012 (1)");
+        assert_eq!(render("{{{kind|capitalize}}}", &b), "{Fantasy, Humour}");
+        assert_eq!(render("{title|upper|truncate 6}", &b), "ACCROS");
+        assert_eq!(render("{title|ellipsis 6}", &b), "Accros...");
+        assert_eq!(render("{authors|first|join \"\"} / {cover}", &b),
+                   "Terry Pratchett / /covers/accros.jpg");
+        assert_eq!(render("{pages? pages \" p.\"}{publisher|default \"?\"}", &b),
+                   "?");
+
+        b.subtitle = "".to_string();
+        b.pubdate = None;
+
+        assert_eq!(render("{title}{subtitle? \": \" subtitle}", &b),
+                   "Accros du roc");
+        assert_eq!(render("{pubdate|%Y}", &b), "");
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let error = |t: &str| Template::parse(t).unwrap_err().to_string();
+
+        assert_eq!(error("{title"),
+                   "Invalid template '{title' at 1: Missing '}'");
+
+        assert_eq!(error("x {isbn13}"),
+                   "Invalid template 'x {isbn13}' at 3: Unknown field 'isbn13'");
+
+        assert_eq!(error("{pubdate|upper}"),
+                   "Invalid template '{pubdate|upper}' at 1: Filter Upper cannot apply to Date");
+
+        assert!(error("{title|join}").contains("Invalid filter 'join'"));
+
+        assert!(error("{pubdate|%Q}").ends_with("Invalid date specifier '%Q' in '%Q'"));
+        assert!(error("{pubdate|%d/%m/%H}").contains("Invalid date specifier '%H'"));
+        assert!(error("{read_end|format \"%-S\"}").contains("Invalid date specifier '%S'"));
+        assert!(error("{pubdate|format \"%Y%\"}").contains("Incomplete date specifier"));
+        assert!(error("{title}}").contains("Unexpected '}'"));
+    }
+}
@@ -0,0 +1,390 @@
+//! OPF package document (`metadata.opf` of Calibre, or `content.opf`
+//! of an EPUB), with its Dublin Core metadata.
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Read, Result};
+
+use time::Date;
+
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::codex::{Book, author_named, author_sorted, empty_book};
+use crate::model::Isbn;
+
+/// Metadata of an OPF package
+pub struct Package {
+    pub book: Book,
+    pub cover: Option<CoverItem>,
+    /// Google Books identifier
+    pub google_id: Option<String>,
+}
+
+/// Cover image of a package
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverItem {
+    /// Location, relative to the package document
+    pub href: String,
+    /// Media type from the manifest (empty if not declared there)
+    pub media_type: String,
+}
+
+/// Child element of the `<metadata>`, `<manifest>` or `<guide>`
+struct Element {
+    /// Local name of the parent section
+    section: String,
+    name: String,
+    /// Attributes by local name
+    attrs: HashMap<String, String>,
+    text: String,
+}
+
+impl Element {
+    fn attr(&self, name: &str) -> &str {
+        self.attrs.get(name).map_or("", |v| v.trim())
+    }
+}
+
+fn invalid<E: std::fmt::Display>(cause: E) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid OPF: {}", cause))
+}
+
+/// Parses the package document.
+pub fn parse<R: Read>(r: R) -> Result<Package> {
+    let elements = read_elements(r)?;
+
+    if !elements.iter().any(|e| e.section == "metadata") {
+        return Err(invalid("missing <metadata>"));
+    }
+
+    let metadata = || elements.iter().filter(|e| e.section == "metadata");
+    let text = |name: &str| metadata().find(|e| e.name == name).
+        map(|e| e.text.trim().to_string()).unwrap_or_default();
+
+    // EPUB 3 refinements (e.g. role or file-as of a creator), by refined ID
+    let mut refines: HashMap<&str, Vec<&Element>> = HashMap::new();
+
+    for e in metadata().filter(|e| e.name == "meta") {
+        if let Some(id) = e.attr("refines").strip_prefix('#') {
+            refines.entry(id).or_default().push(e);
+        }
+    }
+
+    let refined = |e: &Element, property: &str| refines.get(e.attr("id")).
+        and_then(|rs| rs.iter().find(|r| r.attr("property") == property)).
+        map(|r| r.text.trim().to_string()).unwrap_or_default();
+
+    // Calibre (OPF 2) metadata, e.g. `calibre:series`
+    let meta = |name: &str| metadata().
+        find(|e| e.name == "meta" && e.attr("name") == name).
+        map(|e| e.attr("content").to_string());
+
+    let mut book = empty_book();
+
+    book.title = text("title");
+    book.publisher = text("publisher");
+    book.language = text("language");
+    book.summary = text("description");
+
+    book.authors = metadata().filter(|e| e.name == "creator").filter(|e| {
+        let role = match e.attr("role") {
+            "" => refined(e, "role"),
+            r => r.to_string(),
+        };
+
+        role.is_empty() || role == "aut"
+    }).map(|e| {
+        let sort = match e.attr("file-as") {
+            "" => refined(e, "file-as"),
+            s => s.to_string(),
+        };
+
+        if sort.is_empty() {
+            author_named(e.text.trim())
+        } else {
+            author_sorted(&sort, e.text.trim())
+        }
+    }).collect();
+
+    book.tags = metadata().filter(|e| e.name == "subject").
+        map(|e| e.text.trim().to_string()).filter(|s| !s.is_empty()).collect();
+
+    // The publication date, rather than the ones of other events
+    book.pubdate = metadata().filter(|e| e.name == "date").
+        find(|e| matches!(e.attr("event"), "" | "publication")).
+        and_then(|e| parse_date(&e.text));
+
+    let mut google_id = None;
+
+    for e in metadata().filter(|e| e.name == "identifier") {
+        match identifier(e.attr("scheme"), e.text.trim()) {
+            Identifier::Isbn(i) if !book.isbn.contains(&i) => book.isbn.push(i),
+            Identifier::Google(id) => google_id = Some(id),
+            _ => (),
+        }
+    }
+
+    let collection = metadata().find(|e| e.attr("property") == "belongs-to-collection");
+
+    match (meta("calibre:series"), collection) {
+        (Some(series), _) => {
+            book.series = series;
+            book.volume = meta("calibre:series_index").and_then(|i| volume(&i));
+        }
+
+        (None, Some(c)) => {
+            book.series = c.text.trim().to_string();
+            book.volume = volume(&refined(c, "group-position"));
+        }
+
+        (None, None) => (),
+    }
+
+    // Calibre rating, from 0 to 10
+    book.rating = meta("calibre:rating").and_then(|r| r.parse::<f32>().ok()).
+        filter(|r| *r > 0.0).map(|r| r / 2.0);
+
+    let cover = cover(&elements);
+
+    Ok(Package { book, cover, google_id })
+}
+
+fn read_elements<R: Read>(r: R) -> Result<Vec<Element>> {
+    let mut elements = vec![];
+    let mut path: Vec<String> = vec![];
+    let mut current: Option<Element> = None;
+
+    for event in EventReader::new(r) {
+        match event.map_err(invalid)? {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                if path.len() == 2 && path[0] == "package" {
+                    current = Some(Element {
+                        section: path[1].to_string(),
+                        name: name.local_name.to_string(),
+                        attrs: attributes.into_iter().
+                            map(|a| (a.name.local_name, a.value)).collect(),
+                        text: "".to_string(),
+                    });
+                }
+
+                path.push(name.local_name);
+            }
+
+            XmlEvent::Characters(text) | XmlEvent::CData(text) => {
+                if let Some(e) = current.as_mut() {
+                    e.text.push_str(&text);
+                }
+            }
+
+            XmlEvent::EndElement { .. } => {
+                path.pop();
+
+                if path.len() == 2 {
+                    elements.extend(current.take());
+                }
+            }
+
+            _ => (),
+        }
+    }
+
+    Ok(elements)
+}
+
+enum Identifier {
+    Isbn(Isbn),
+    Google(String),
+    Other,
+}
+
+/// Identifies an ISBN or a Google Books ID, either from its scheme
+/// (e.g. `opf:scheme="ISBN"`), its prefix (e.g. `urn:isbn:`),
+/// or its value for an unknown scheme.
+fn identifier(scheme: &str, value: &str) -> Identifier {
+    let lower = value.to_ascii_lowercase();
+    let scheme = scheme.to_ascii_lowercase();
+
+    if scheme == "google" {
+        return Identifier::Google(value.to_string());
+    } else if let Some(id) = lower.strip_prefix("google:") {
+        return Identifier::Google(value[value.len() - id.len()..].to_string());
+    }
+
+    let (isbn, explicit) = match (lower.strip_prefix("urn:isbn:").
+                                  or_else(|| lower.strip_prefix("isbn:")),
+                                  scheme.as_str()) {
+        (Some(i), _) => (&value[value.len() - i.len()..], true),
+        (None, "isbn") => (value, true),
+        (None, "") => (value, false),
+        _ => return Identifier::Other,
+    };
+
+    match Isbn::parse(isbn.trim()) {
+        Ok(i) => Identifier::Isbn(i),
+
+        Err(cause) => {
+            if explicit {
+                log::warn!(target: "opf", "Invalid ISBN '{}': {}", value, cause);
+            }
+
+            Identifier::Other
+        }
+    }
+}
+
+/// Returns the volume number from a series index (e.g. "27" or "27.0").
+fn volume(index: &str) -> Option<u16> {
+    index.trim().parse::<f32>().ok().
+        filter(|i| i.fract() == 0.0 && *i >= 0.0 && *i <= u16::MAX as f32).
+        map(|i| i as u16)
+}
+
+/// Returns the location and media type of the cover image,
+/// from the manifest (EPUB 3 `cover-image` item, or EPUB 2 `cover` meta),
+/// or else from the guide (Calibre).
+fn cover(elements: &[Element]) -> Option<CoverItem> {
+    let manifest = || elements.iter().
+        filter(|e| e.section == "manifest" && e.name == "item");
+
+    let cover_id = elements.iter().find(|e| e.section == "metadata" &&
+                                        e.name == "meta" &&
+                                        e.attr("name") == "cover").
+        map(|e| e.attr("content"));
+
+    let item = manifest().find(|e| e.attr("properties").split(' ').any(|p| p == "cover-image")).
+        or_else(|| cover_id.and_then(|id| manifest().find(|e| e.attr("id") == id))).
+        or_else(|| elements.iter().find(|e| {
+            let href = e.attr("href").to_ascii_lowercase();
+
+            e.section == "guide" && e.attr("type") == "cover" &&
+                [ ".jpg", ".jpeg", ".png", ".gif", ".webp" ].iter().
+                any(|ext| href.ends_with(ext))
+        }))?;
+
+    let href = item.attr("href");
+
+    // Media type of a guide reference from the manifest, if listed there
+    let media_type = match item.attr("media-type") {
+        "" => manifest().find(|e| e.attr("href") == href).
+            map_or("", |e| e.attr("media-type")),
+        t => t,
+    };
+
+    Some(CoverItem {
+        href: href.to_string(),
+        media_type: media_type.to_string(),
+    }).filter(|c| !c.href.is_empty())
+}
+
+/// Parses an OPF date (`YYYY`, `YYYY-MM`, `YYYY-MM-DD`,
+/// possibly followed by a time), the missing month or day being the first.
+pub(crate) fn parse_date(value: &str) -> Option<Date> {
+    let date = value.trim().split(['T', ' ']).next()?;
+
+    if date.is_empty() {
+        return None;
+    }
+
+    let mut parts = date.split('-');
+
+    let parsed = (|| {
+        let year: i32 = parts.next()?.parse().ok()?;
+        let month: u8 = parts.next().map_or(Some(1), |m| m.parse().ok())?;
+        let day: u8 = parts.next().map_or(Some(1), |d| d.parse().ok())?;
+
+        Date::try_from_ymd(year, month, day).ok()
+    })();
+
+    match parsed {
+        // Calibre "undefined" date (year 101)
+        Some(d) if d.year() <= 101 => None,
+
+        Some(d) => Some(d),
+
+        None => {
+            log::warn!(target: "opf", "Invalid date '{}'", value.trim());
+
+            None
+        }
+    }
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CALIBRE_OPF: &str = r#"<?xml version='1.0' encoding='utf-8'?>
+<package xmlns="http://www.idpf.org/2007/opf" unique-identifier="uuid_id" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+    <dc:identifier opf:scheme="calibre" id="calibre_id">12</dc:identifier>
+    <dc:identifier opf:scheme="uuid" id="uuid_id">5f9e1b0e-5c44-4bd1-9d3e-6a3c3c2b1f0a</dc:identifier>
+    <dc:title>Accros du roc</dc:title>
+    <dc:creator opf:file-as="Pratchett, Terry" opf:role="aut">Terry Pratchett</dc:creator>
+    <dc:contributor opf:file-as="calibre" opf:role="bkp">calibre (5.44.0)</dc:contributor>
+    <dc:date>2004-01-07T23:00:00+00:00</dc:date>
+    <dc:description>&lt;p&gt;Suzanne Sto Hélit.&lt;/p&gt;</dc:description>
+    <dc:publisher>L'Atalante</dc:publisher>
+    <dc:identifier opf:scheme="ISBN">978-2-84172-048-4</dc:identifier>
+    <dc:identifier opf:scheme="GOOGLE">fwIHPwAACAAJ</dc:identifier>
+    <dc:language>fra</dc:language>
+    <dc:subject>Fantasy</dc:subject>
+    <dc:subject>Humour</dc:subject>
+    <meta name="calibre:rating" content="8"/>
+    <meta name="calibre:series" content="Les Annales du Disque-monde"/>
+    <meta name="calibre:series_index" content="27.0"/>
+  </metadata>
+  <guide>
+    <reference type="cover" title="Couverture" href="cover.jpg"/>
+  </guide>
+</package>"#;
+
+    #[test]
+    fn test_parse_calibre() {
+        let p = parse(CALIBRE_OPF.as_bytes()).unwrap();
+        let b = &p.book;
+
+        assert_eq!(b.title, "Accros du roc");
+        assert_eq!(b.authors.len(), 1);
+        assert_eq!(b.authors[0].name, "Terry Pratchett");
+        assert_eq!(b.authors[0].first_name, "Terry");
+        assert_eq!(b.authors[0].last_name, "Pratchett");
+        assert_eq!(b.pubdate, Some(time::date!(2004-01-07)));
+        assert_eq!(b.summary, "<p>Suzanne Sto Hélit.</p>");
+        assert_eq!(b.publisher, "L'Atalante");
+        assert_eq!(b.isbn.iter().map(Isbn::digits).collect::<Vec<String>>(),
+                   vec![ "9782841720484" ]);
+        assert_eq!(b.language, "fra");
+        assert_eq!(b.tags, vec![ "Fantasy", "Humour" ]);
+        assert_eq!(b.rating, Some(4.0));
+        assert_eq!(b.series, "Les Annales du Disque-monde");
+        assert_eq!(b.volume, Some(27));
+
+        assert_eq!(p.cover, Some(CoverItem {
+            href: "cover.jpg".to_string(),
+            media_type: "".to_string(),
+        }));
+        assert_eq!(p.google_id, Some("fwIHPwAACAAJ".to_string()));
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("2004"), Some(time::date!(2004-01-01)));
+        assert_eq!(parse_date("2004-03"), Some(time::date!(2004-03-01)));
+        assert_eq!(parse_date("2004-03-14 10:00:00+00:00"),
+                   Some(time::date!(2004-03-14)));
+        assert_eq!(parse_date("0101-01-01T00:00:00+00:00"), None);
+        assert_eq!(parse_date("mars 2004"), None);
+        assert_eq!(parse_date(""), None);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!(parse("<package><manifest/></package>".as_bytes()).
+                   err().map(|e| e.to_string()),
+                   Some("Invalid OPF: missing <metadata>".to_string()));
+
+        assert!(parse("<package><metadata>".as_bytes()).err().
+                map(|e| e.to_string()).unwrap().starts_with("Invalid OPF: "));
+    }
+}
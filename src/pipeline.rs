@@ -0,0 +1,390 @@
+//! Conversion of the books from a source (e.g. a Codex XML export)
+//! to one or more sinks (e.g. Mylib CSV), through optional transforms
+//! and cover resolution.
+
+use std::fmt::{Display, Formatter};
+use std::io::{Error, Result};
+use std::sync::Arc;
+
+use crate::codex::{Book, Warning};
+use crate::cover::{self, Cover, CoverFetcher};
+
+/// Source of books, in the order of the input.
+pub trait BookSource {
+    /// Returns the next book, or `None` at the end of the source.
+    ///
+    /// An `Err` is reported by the pipeline, which then asks for the next
+    /// book (the source ending if it cannot recover).
+    fn next_book(&mut self) -> Option<Result<Book>>;
+
+    /// Returns the warnings about the fields of the book last returned.
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        Vec::new()
+    }
+}
+
+/// Destination of books, finalized once all of them are written.
+pub trait BookSink {
+    /// Writes the book, returning the cause of a partial write if any
+    /// (e.g. a cover which cannot be resolved).
+    /// An `Err` means the book is skipped by this sink.
+    fn write(&mut self, entry: &Entry) -> Result<Option<Error>>;
+
+    /// Finalizes the output (e.g. closing the document, flushing).
+    fn finish(&mut self) -> Result<()>;
+}
+
+/// Book going through the pipeline
+pub struct Entry {
+    pub book: Book,
+    /// Cover, if resolved by the pipeline (see `Pipeline::covers`)
+    pub cover: Option<Result<Cover>>,
+    /// Warnings about the book fields, from the source
+    pub warnings: Vec<Warning>,
+}
+
+/// Progress of a pipeline, as notified to its observer
+pub enum Event<'a> {
+    /// The source fails to read a book.
+    Invalid(Error),
+
+    /// A book is read (and kept by the transforms).
+    Read(&'a Entry),
+
+    /// A book is written to the sink with the given index,
+    /// possibly partially (see `BookSink::write`).
+    Written {
+        sink: usize,
+        entry: &'a Entry,
+        issue: Option<Error>,
+    },
+
+    /// A book cannot be written to the sink with the given index.
+    Skipped {
+        sink: usize,
+        entry: &'a Entry,
+        cause: Error,
+    },
+
+    /// The sink with the given index cannot be finalized.
+    Unfinished {
+        sink: usize,
+        cause: Error,
+    },
+}
+
+impl Display for Event<'_> {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::Invalid(cause) => write!(formatter, "Invalid book: {}", cause),
+
+            Event::Read(entry) => write!(formatter, "Read {}", entry.book.title),
+
+            Event::Written { sink, entry, issue: None } =>
+                write!(formatter, "Written to #{}: {}", sink, entry.book.title),
+
+            Event::Written { sink, entry, issue: Some(issue) } =>
+                write!(formatter, "Partially written to #{}: {}: {}",
+                       sink, entry.book.title, issue),
+
+            Event::Skipped { sink, entry, cause } =>
+                write!(formatter, "Skipped by #{}: {}: {}",
+                       sink, entry.book.title, cause),
+
+            Event::Unfinished { sink, cause } =>
+                write!(formatter, "Fails to finish #{}: {}", sink, cause),
+        }
+    }
+}
+
+type Transform<'a> = Box<dyn FnMut(Book) -> Option<Book> + 'a>;
+
+/// Returns the URL and ISBN to fetch the cover of a book, if any
+/// (e.g. `mylib::cover_request`)
+type CoverRequest<'a> = Box<dyn Fn(&Book) -> Option<(String, String)> + 'a>;
+
+/// Chain from a source to sinks:
+/// each book read from the source goes through the transforms,
+/// gets its cover resolved (if enabled), and is written to every sink.
+pub struct Pipeline<'a> {
+    source: Box<dyn BookSource + 'a>,
+    transforms: Vec<Transform<'a>>,
+    covers: Option<(CoverRequest<'a>, Arc<CoverFetcher>, usize)>,
+    sinks: Vec<Box<dyn BookSink + 'a>>,
+}
+
+impl<'a> Pipeline<'a> {
+    pub fn new<S: BookSource + 'a>(source: S) -> Pipeline<'a> {
+        Pipeline {
+            source: Box::new(source),
+            transforms: vec![],
+            covers: None,
+            sinks: vec![],
+        }
+    }
+
+    /// Transforms the books.
+    pub fn map<F: FnMut(Book) -> Book + 'a>(mut self, mut f: F) -> Pipeline<'a> {
+        self.transforms.push(Box::new(move |b| Some(f(b))));
+        self
+    }
+
+    /// Only keeps the books matching the predicate.
+    pub fn filter<F: FnMut(&Book) -> bool + 'a>(
+        mut self,
+        mut predicate: F,
+    ) -> Pipeline<'a> {
+        self.transforms.push(Box::new(
+            move |b| if predicate(&b) { Some(b) } else { None }));
+        self
+    }
+
+    /// Resolves the covers of the books, as given by the `request`
+    /// (e.g. `mylib::cover_request`), using at most `concurrency` workers.
+    pub fn covers<R: Fn(&Book) -> Option<(String, String)> + 'a>(
+        mut self,
+        request: R,
+        fetcher: Arc<CoverFetcher>,
+        concurrency: usize,
+    ) -> Pipeline<'a> {
+        self.covers = Some((Box::new(request), fetcher, concurrency));
+        self
+    }
+
+    /// Adds a sink, to which all the books are written.
+    pub fn sink<S: BookSink + 'a>(mut self, sink: S) -> Pipeline<'a> {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Runs the conversion, notifying the observer of the progress,
+    /// and finally finishes the sinks.
+    pub fn run<F: FnMut(Event)>(self, mut observer: F) {
+        let Pipeline { mut source, mut transforms, covers, mut sinks } = self;
+
+        let books = std::iter::from_fn(|| loop {
+            match source.next_book()? {
+                Ok(book) => {
+                    let warnings = source.take_warnings();
+                    let kept = transforms.iter_mut().
+                        try_fold(book, |b, transform| transform(b));
+
+                    if let Some(b) = kept {
+                        return Some(Ok((b, warnings)));
+                    }
+                }
+
+                Err(cause) => return Some(Err(cause)),
+            }
+        });
+
+        let entries: Box<dyn Iterator<Item = Result<Entry>>> = match covers {
+            Some((request, fetcher, concurrency)) => Box::new(cover::pool::prefetch(
+                books,
+                move |res| res.as_ref().ok().and_then(|(b, _)| request(b)),
+                fetcher,
+                concurrency).map(|(res, cover)| res.map(
+                    |(book, warnings)| Entry { book, cover, warnings }))),
+
+            None => Box::new(books.map(|res| res.map(
+                |(book, warnings)| Entry { book, cover: None, warnings }))),
+        };
+
+        for res in entries {
+            let entry = match res {
+                Ok(e) => e,
+
+                Err(cause) => {
+                    observer(Event::Invalid(cause));
+
+                    continue;
+                }
+            };
+
+            observer(Event::Read(&entry));
+
+            for (sink, s) in sinks.iter_mut().enumerate() {
+                match s.write(&entry) {
+                    Ok(issue) => observer(Event::Written { sink, entry: &entry, issue }),
+                    Err(cause) => observer(Event::Skipped { sink, entry: &entry, cause }),
+                }
+            }
+        }
+
+        for (sink, s) in sinks.iter_mut().enumerate() {
+            if let Err(cause) = s.finish() {
+                observer(Event::Unfinished { sink, cause });
+            }
+        }
+    }
+}
+
+impl<S: BookSource + ?Sized> BookSource for Box<S> {
+    fn next_book(&mut self) -> Option<Result<Book>> {
+        (**self).next_book()
+    }
+
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        (**self).take_warnings()
+    }
+}
+
+impl<S: BookSink + ?Sized> BookSink for Box<S> {
+    fn write(&mut self, entry: &Entry) -> Result<Option<Error>> {
+        (**self).write(entry)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        (**self).finish()
+    }
+}
+
+/// Source of the books yielded by an iterator (e.g. `mylib::read`)
+pub struct Books<I>(pub I);
+
+impl<I: Iterator<Item = Result<Book>>> BookSource for Books<I> {
+    fn next_book(&mut self) -> Option<Result<Book>> {
+        self.0.next()
+    }
+}
+
+// ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::io::ErrorKind;
+    use std::rc::Rc;
+
+    use crate::codex::empty_book;
+
+    fn book(title: &str) -> Book {
+        let mut b = empty_book();
+
+        b.title = title.to_string();
+
+        b
+    }
+
+    /// Sink recording the titles, failing on the given one
+    struct Titles {
+        titles: Rc<RefCell<Vec<String>>>,
+        fails_on: &'static str,
+        finished: bool,
+    }
+
+    impl BookSink for Titles {
+        fn write(&mut self, entry: &Entry) -> Result<Option<Error>> {
+            if entry.book.title == self.fails_on {
+                Err(Error::new(ErrorKind::InvalidData, "Unsupported"))
+            } else {
+                self.titles.borrow_mut().push(entry.book.title.to_string());
+
+                Ok(None)
+            }
+        }
+
+        fn finish(&mut self) -> Result<()> {
+            if self.finished {
+                Err(Error::other("Already finished"))
+            } else {
+                self.finished = true;
+
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_fan_out() {
+        let source = Books(vec![
+            Ok(book("Accros du roc")),
+            Err(Error::new(ErrorKind::InvalidData, "Invalid record")),
+            Ok(book("Ally")),
+            Ok(book("Vulture Peak")),
+        ].into_iter());
+
+        let first = Rc::new(RefCell::new(vec![]));
+        let second = Rc::new(RefCell::new(vec![]));
+        let mut events = vec![];
+
+        Pipeline::new(source).
+            filter(|b| b.title != "Vulture Peak").
+            map(|mut b| {
+                b.title = b.title.to_uppercase();
+                b
+            }).
+            sink(Titles {
+                titles: first.clone(),
+                fails_on: "",
+                finished: false,
+            }).
+            sink(Titles {
+                titles: second.clone(),
+                fails_on: "ALLY",
+                finished: true,
+            }).
+            run(|e| events.push(e.to_string()));
+
+        assert_eq!(*first.borrow(), vec![ "ACCROS DU ROC", "ALLY" ]);
+        assert_eq!(*second.borrow(), vec![ "ACCROS DU ROC" ]);
+
+        assert_eq!(events.len(), 8);
+        assert_eq!(events[0].as_str(), "Read ACCROS DU ROC");
+        assert_eq!(events[3].as_str(), "Invalid book: Invalid record");
+        assert_eq!(events[6].as_str(), "Skipped by #1: ALLY: Unsupported");
+        assert_eq!(events[7].as_str(), "Fails to finish #1: Already finished");
+    }
+
+    /// Title and cover data (if requested) of the written books
+    type Written = Vec<(String, Option<Vec<u8>>)>;
+
+    /// Sink recording the titles with the cover data
+    struct Covers(Rc<RefCell<Written>>);
+
+    impl BookSink for Covers {
+        fn write(&mut self, entry: &Entry) -> Result<Option<Error>> {
+            let cover = entry.cover.as_ref().
+                map(|c| c.as_ref().map_or(vec![], |c| c.data.clone()));
+
+            self.0.borrow_mut().push((entry.book.title.to_string(), cover));
+
+            Ok(None)
+        }
+
+        fn finish(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_covers() {
+        let mut roc = book("Accros du roc");
+        let mut ally = book("Ally");
+
+        roc.cover = "data:image/png;base64,iVBORw==".to_string();
+        ally.cover = "data:image/png;base64,AAAA".to_string();
+
+        let fetcher = CoverFetcher::new(
+            reqwest::blocking::Client::new(), None, cover::CacheMode::Offline);
+
+        let covers = Rc::new(RefCell::new(vec![]));
+
+        // No cover requested for Ally
+        Pipeline::new(Books(vec![ Ok(roc), Ok(ally) ].into_iter())).
+            covers(|b| if b.title == "Ally" {
+                None
+            } else {
+                Some((b.cover.to_string(), "".to_string()))
+            }, Arc::new(fetcher), 2).
+            sink(Covers(covers.clone())).
+            run(|_| ());
+
+        assert_eq!(*covers.borrow(), vec![
+            ("Accros du roc".to_string(), Some(b"\x89PNG".to_vec())),
+            ("Ally".to_string(), None),
+        ]);
+    }
+}